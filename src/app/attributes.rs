@@ -0,0 +1,243 @@
+use anyhow::{anyhow, Context, Result};
+use git2::{Repository, Tree};
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// `diff` attribute of .gitattributes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffAttribute {
+    Unspecified,
+    Set,            // `diff`: always shown as text
+    Unset,          // `-diff` or `binary`: always shown as binary
+    Driver(String), // `diff=<driver>`
+}
+
+impl From<AttributeValue> for DiffAttribute {
+    fn from(value: AttributeValue) -> Self {
+        match value {
+            AttributeValue::Unspecified => Self::Unspecified,
+            AttributeValue::Set => Self::Set,
+            AttributeValue::Unset => Self::Unset,
+            AttributeValue::Value(driver) => Self::Driver(driver),
+        }
+    }
+}
+
+// a state of an attribute for a path, cf. https://git-scm.com/docs/gitattributes#_description
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeValue {
+    Unspecified,
+    Set,           // `name`
+    Unset,         // `-name`
+    Value(String), // `name=value`
+}
+
+impl AttributeValue {
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Self::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+// Read .gitattributes files in a tree, from the root to the directory of the path, and then
+// $GIT_DIR/info/attributes, so that attributes at the browsed commit are used.
+pub fn attribute_value(path: &str, tree: &Tree, repo: &Repository, name: &str) -> AttributeValue {
+    let mut attribute = AttributeValue::Unspecified;
+    let mut dir = String::new();
+    let mut check = |content: &[u8], dir: &str| {
+        let relative_path = &path[dir.len()..];
+        for line in String::from_utf8_lossy(content).lines() {
+            if let Some(value) = parse_line(line, relative_path, name) {
+                attribute = value;
+            }
+        }
+    };
+
+    for component in path.split('/') {
+        let attributes_path = format!("{}.gitattributes", dir);
+        if let Some(blob) = tree
+            .get_path(Path::new(&attributes_path))
+            .and_then(|entry| entry.to_object(repo))
+            .ok()
+            .and_then(|object| object.into_blob().ok())
+        {
+            check(blob.content(), &dir);
+        }
+        dir.push_str(component);
+        dir.push('/');
+    }
+    if let Ok(content) = fs::read(repo.path().join("info").join("attributes")) {
+        check(&content, "");
+    }
+
+    attribute
+}
+
+// Run `diff.<driver>.textconv` with a temporary file of a blob as git does.
+pub fn run_textconv(command: &str, content: &[u8], repo: &Repository) -> Result<Vec<u8>> {
+    let file = TempFile::new(content).context("Failed to write a temporary file for textconv")?;
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", command))
+        .arg(command)
+        .arg(&file.path)
+        .current_dir(repo.workdir().unwrap_or_else(|| repo.path()))
+        .output()
+        .with_context(|| format!("Failed to run '{}'", command))?;
+    if !output.status.success() {
+        return Err(anyhow!("'{}' exited with {}", command, output.status));
+    }
+    Ok(output.stdout)
+}
+
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+// a temporary file, which is removed when dropped
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    // Create a file only readable by the user, with an unpredictable name and without following
+    // an existing file or symlink.
+    fn new(content: &[u8]) -> io::Result<Self> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        for _ in 0..TEMP_FILE_ATTEMPTS {
+            let path = env::temp_dir().join(format!("git-hist-{:016x}", random_number()));
+            match options.open(&path) {
+                Ok(mut file) => {
+                    let temp_file = Self { path };
+                    file.write_all(content)?;
+                    return Ok(temp_file);
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "Failed to find an unused name",
+        ))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// a number from randomly seeded keys of `RandomState`
+fn random_number() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    if let Ok(duration) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(duration.as_nanos());
+    }
+    hasher.finish()
+}
+
+// the last value of the attribute in a line matching the path
+fn parse_line(line: &str, path: &str, name: &str) -> Option<AttributeValue> {
+    let mut tokens = line.split_whitespace();
+    let pattern = tokens.next().filter(|pattern| !pattern.starts_with('#'))?;
+    if !matches_pattern(pattern, path) {
+        return None;
+    }
+    tokens
+        .filter_map(|token| match token {
+            _ if token == name => Some(AttributeValue::Set),
+            // the macro for `-diff -merge -text`
+            "binary" if ["diff", "merge", "text"].contains(&name) => Some(AttributeValue::Unset),
+            _ if token.strip_prefix('-') == Some(name) => Some(AttributeValue::Unset),
+            _ if token.strip_prefix('!') == Some(name) => Some(AttributeValue::Unspecified),
+            _ => token
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('='))
+                .map(|value| AttributeValue::Value(value.to_string())),
+        })
+        .next_back()
+}
+
+// cf. https://git-scm.com/docs/gitattributes#_description
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    if pattern.ends_with('/') {
+        // directories are not files
+        return false;
+    }
+    if pattern.contains('/') {
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        wildmatch(pattern.as_bytes(), path.as_bytes())
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        wildmatch(pattern.as_bytes(), name.as_bytes())
+    }
+}
+
+// `*` and `?` do not match `/`, and `**` matches any directories
+fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+            (0..=text.len())
+                .filter(|i| *i == 0 || text[i - 1] == b'/' || rest.is_empty())
+                .any(|i| wildmatch(rest, &text[i..]))
+        }
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|i| *i == 0 || text[i - 1] != b'/')
+            .any(|i| wildmatch(rest, &text[i..])),
+        [b'?', rest @ ..] => {
+            matches!(text.first(), Some(c) if *c != b'/') && wildmatch(rest, &text[1..])
+        }
+        [b'[', rest @ ..] => match text.first() {
+            Some(c) => match match_class(rest, *c) {
+                Some((is_matched, rest)) => is_matched && wildmatch(rest, &text[1..]),
+                // an unclosed bracket is a literal
+                None => *c == b'[' && wildmatch(rest, &text[1..]),
+            },
+            None => false,
+        },
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => {
+            text.first() == Some(c) && wildmatch(rest, &text[1..])
+        }
+    }
+}
+
+// Match a character with a class like `[a-z]` or `[!0-9]`, and return the rest of the pattern.
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, &[u8])> {
+    let (is_negated, pattern) = match pattern {
+        [b'!', rest @ ..] | [b'^', rest @ ..] => (true, rest),
+        _ => (false, pattern),
+    };
+    // `]` at the start is a literal
+    let end = pattern.iter().skip(1).position(|c| *c == b']')? + 1;
+    let class = &pattern[..end];
+    let mut is_matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == b'-' {
+            is_matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            is_matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    Some((is_matched != is_negated, &pattern[end + 1..]))
+}
@@ -1,26 +1,34 @@
+use crate::app::reference::{Containment, ReferenceIndex, References};
 use chrono::TimeZone;
 use git2::{Commit as GitCommit, Oid, Repository};
-use itertools::Itertools;
-use once_cell::sync::OnceCell;
-use std::{collections::HashMap, fmt};
-
-const HEAD_NAME: &str = "HEAD";
 
 pub struct Commit<'a> {
     oid: Oid,
     short_id: String,
     long_id: String,
     author_name: String,
+    author_email: String,
     author_date: chrono::DateTime<chrono::Local>,
+    author_offset: chrono::FixedOffset, // the timezone of the author
     committer_name: String,
+    committer_email: String,
     committer_date: chrono::DateTime<chrono::Local>,
+    committer_offset: chrono::FixedOffset,
     summary: String,
-    references: OnceCell<References>,
+    message: String,
+    parent_ids: Vec<String>,
+    trailers: Vec<Trailer>,
+    references: &'a References,
+    reference_index: &'a ReferenceIndex,
     repo: &'a Repository,
 }
 
 impl<'a> Commit<'a> {
-    pub fn new(commit: &GitCommit, repo: &'a Repository) -> Self {
+    pub fn new(
+        commit: &GitCommit,
+        repo: &'a Repository,
+        reference_index: &'a ReferenceIndex,
+    ) -> Self {
         let oid = commit.id();
         let short_id = commit
             .as_object()
@@ -31,29 +39,58 @@ impl<'a> Commit<'a> {
             .to_string();
         let long_id = format!("{}", oid);
         let author = commit.author().name().unwrap_or_default().to_string();
+        let author_email = commit.author().email().unwrap_or_default().to_string();
         let author_date = chrono::DateTime::<chrono::Local>::from(
             chrono::Utc.timestamp(commit.author().when().seconds(), 0),
         );
+        let author_offset = to_offset(commit.author().when());
         let committer = commit.committer().name().unwrap_or_default().to_string();
+        let committer_email = commit.committer().email().unwrap_or_default().to_string();
         let committer_date = chrono::DateTime::<chrono::Local>::from(
             chrono::Utc.timestamp(commit.committer().when().seconds(), 0),
         );
+        let committer_offset = to_offset(commit.committer().when());
         let summary = commit.summary().unwrap_or_default().to_string();
+        let message = commit.message().unwrap_or_default();
+        let parent_ids = commit.parent_ids().map(|oid| format!("{}", oid)).collect();
+        let trailers = git2::message_trailers_strs(message)
+            .map(|trailers| {
+                trailers
+                    .iter()
+                    .map(|(key, value)| Trailer::new(key, value))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let message = message.trim_end().to_string();
+
+        let references = reference_index.get(oid);
 
         Self {
             oid,
             short_id,
             long_id,
             author_name: author,
+            author_email,
             author_date,
+            author_offset,
             committer_name: committer,
+            committer_email,
             committer_date,
+            committer_offset,
             summary,
-            references: OnceCell::new(),
+            message,
+            parent_ids,
+            trailers,
+            references,
+            reference_index,
             repo,
         }
     }
 
+    pub fn oid(&self) -> Oid {
+        self.oid
+    }
+
     pub fn short_id(&self) -> &str {
         &self.short_id
     }
@@ -66,200 +103,86 @@ impl<'a> Commit<'a> {
         &self.author_name
     }
 
+    pub fn author_email(&self) -> &str {
+        &self.author_email
+    }
+
     pub fn author_date(&self) -> &chrono::DateTime<chrono::Local> {
         &self.author_date
     }
 
+    // in the timezone of the author, not the local one
+    pub fn author_date_with_offset(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        self.author_date.with_timezone(&self.author_offset)
+    }
+
     pub fn committer_name(&self) -> &str {
         &self.committer_name
     }
 
-    pub fn committer_date(&self) -> &chrono::DateTime<chrono::Local> {
-        &self.committer_date
+    pub fn committer_email(&self) -> &str {
+        &self.committer_email
     }
 
-    pub fn summary(&self) -> &str {
-        &self.summary
-    }
-
-    pub fn references(&self) -> &References {
-        self.references.get_or_init(|| self.calc_references())
+    pub fn committer_date(&self) -> &chrono::DateTime<chrono::Local> {
+        &self.committer_date
     }
 
-    fn calc_references(&self) -> References {
-        let head = self.repo.head().unwrap();
-
-        let references = self
-            .repo
-            .references()
-            .unwrap()
-            .filter_map(|r| r.ok())
-            .filter(|r| {
-                r.target()
-                    // use https://doc.rust-lang.org/std/option/enum.Option.html#method.contains in the future
-                    .filter(|oid| *oid == self.oid)
-                    .is_some()
-            });
-        let reference_groups: HashMap<ReferenceType, Vec<_>> =
-            references.into_group_map_by(|r| match r {
-                _ if r.is_branch() => ReferenceType::LocalBranch,
-                _ if r.is_remote() => ReferenceType::RemoteBranch,
-                _ if r.is_tag() => ReferenceType::Tag,
-                _ => unreachable!(),
-            });
-
-        let local_branches: Vec<LocalBranch> = reference_groups
-            .get(&ReferenceType::LocalBranch)
-            .map(|rs| rs.iter().collect::<Vec<_>>())
-            .unwrap_or_else(Vec::new)
-            .iter()
-            .filter_map(|r| {
-                r.shorthand()
-                    .map(|name| LocalBranch::new(name, r.name() == head.name()))
-            })
-            .collect();
-
-        let remote_branches: Vec<RemoteBranch> = reference_groups
-            .get(&ReferenceType::RemoteBranch)
-            .map(|rs| rs.iter().collect::<Vec<_>>())
-            .unwrap_or_else(Vec::new)
-            .iter()
-            .filter_map(|r| r.shorthand().map(RemoteBranch::new))
-            .collect();
-
-        let tags: Vec<Tag> = reference_groups
-            .get(&ReferenceType::Tag)
-            .map(|rs| rs.iter().collect::<Vec<_>>())
-            .unwrap_or_else(Vec::new)
-            .iter()
-            .filter_map(|r| r.shorthand().map(Tag::new))
-            .collect();
-
-        let is_head = head.target().unwrap() == self.oid && head.name() == Some(HEAD_NAME);
-
-        References::new(local_branches, remote_branches, tags, is_head)
+    pub fn committer_date_with_offset(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        self.committer_date.with_timezone(&self.committer_offset)
     }
-}
 
-#[derive(Debug)]
-pub struct References {
-    local_branches: Vec<LocalBranch>,
-    remote_branches: Vec<RemoteBranch>,
-    tags: Vec<Tag>,
-    is_head: bool,
-}
-
-impl References {
-    pub fn new(
-        local_branches: Vec<LocalBranch>,
-        remote_branches: Vec<RemoteBranch>,
-        tags: Vec<Tag>,
-        is_head: bool,
-    ) -> Self {
-        Self {
-            local_branches,
-            remote_branches,
-            tags,
-            is_head,
-        }
+    pub fn summary(&self) -> &str {
+        &self.summary
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.local_branches.is_empty()
-            && self.remote_branches.is_empty()
-            && self.tags.is_empty()
-            && !self.is_head
+    pub fn message(&self) -> &str {
+        &self.message
     }
 
-    pub fn head_names(&self) -> Vec<String> {
-        if self.is_head {
-            vec![String::from(HEAD_NAME)]
-        } else {
-            vec![]
-        }
+    pub fn parent_ids(&self) -> &Vec<String> {
+        &self.parent_ids
     }
 
-    pub fn local_branch_names(&self) -> Vec<String> {
-        self.local_branches
-            .iter()
-            .map(|x| format!("{}", x))
-            .collect()
+    pub fn trailers(&self) -> &Vec<Trailer> {
+        &self.trailers
     }
 
-    pub fn remote_branch_names(&self) -> Vec<String> {
-        self.remote_branches
-            .iter()
-            .map(|x| format!("{}", x))
-            .collect()
+    pub fn references(&self) -> &References {
+        self.references
     }
 
-    pub fn tag_names(&self) -> Vec<String> {
-        self.tags.iter().map(|x| format!("{}", x)).collect()
+    pub fn containment(&self) -> &Containment {
+        self.reference_index
+            .containment(self.repo, self.oid, self.committer_date.timestamp())
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-enum ReferenceType {
-    LocalBranch,
-    RemoteBranch,
-    Tag,
+fn to_offset(time: git2::Time) -> chrono::FixedOffset {
+    chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| chrono::FixedOffset::east(0))
 }
 
+// e.g. `Signed-off-by: Name <email>`
 #[derive(Debug)]
-pub struct LocalBranch {
-    name: String,
-    is_head: bool,
+pub struct Trailer {
+    key: String,
+    value: String,
 }
 
-impl LocalBranch {
-    pub fn new(name: impl Into<String>, is_head: bool) -> Self {
+impl Trailer {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
         Self {
-            name: name.into(),
-            is_head,
+            key: key.into(),
+            value: value.into(),
         }
     }
-}
 
-impl fmt::Display for LocalBranch {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.is_head {
-            write!(f, "{} -> {}", HEAD_NAME, self.name)
-        } else {
-            write!(f, "{}", self.name)
-        }
+    pub fn key(&self) -> &str {
+        &self.key
     }
-}
-
-#[derive(Debug)]
-pub struct RemoteBranch {
-    name: String,
-}
-
-impl RemoteBranch {
-    pub fn new(name: impl Into<String>) -> Self {
-        Self { name: name.into() }
-    }
-}
-
-impl fmt::Display for RemoteBranch {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.name)
-    }
-}
-
-#[derive(Debug)]
-pub struct Tag {
-    name: String,
-}
-
-impl Tag {
-    pub fn new(name: impl Into<String>) -> Self {
-        Self { name: name.into() }
-    }
-}
 
-impl fmt::Display for Tag {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "tag: {}", self.name)
+    pub fn value(&self) -> &str {
+        &self.value
     }
 }
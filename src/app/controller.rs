@@ -1,9 +1,19 @@
-use crate::app::history::History;
-use crate::app::state::State;
+use crate::app::history::{History, Location};
+use crate::app::state::{Popup, State};
 use anyhow::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 
-pub fn poll_next_event<'a>(state: State<'a>, history: &'a History) -> Result<Option<State<'a>>> {
+pub enum Control<'h, 'a> {
+    Continue(State<'h, 'a>),
+    Open(State<'h, 'a>, Location),
+    Back(State<'h, 'a>),
+    Quit,
+}
+
+pub fn poll_next_event<'h, 'a>(
+    state: State<'h, 'a>,
+    history: &'h History<'a>,
+) -> Result<Control<'h, 'a>> {
     match event::read()? {
         Event::Key(event) => match event {
             KeyEvent {
@@ -17,44 +27,143 @@ pub fn poll_next_event<'a>(state: State<'a>, history: &'a History) -> Result<Opt
             | KeyEvent {
                 code: KeyCode::Char('q'),
                 modifiers: _,
-            } => Ok(None),
+            } => Ok(Control::Quit),
+            KeyEvent {
+                code: KeyCode::Esc,
+                modifiers: _,
+            } => Ok(Control::Continue(state.cancel())),
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: _,
+            } => Ok(Control::Continue(if state.popup().is_some() {
+                state.select_changed_file()
+            } else {
+                state.toggle_fold()
+            })),
+            KeyEvent {
+                code: KeyCode::Char('t'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_popup(Popup::TagMessage))),
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_popup(Popup::CommitDetails))),
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_popup(Popup::ChangedFiles))),
+            KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.cycle_diff_algorithm())),
+            KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.cycle_whitespace_mode())),
+            KeyEvent {
+                code: KeyCode::Char('b'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_ignore_blank_lines())),
+            KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.cycle_granularity())),
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_word_diff())),
+            KeyEvent {
+                code: KeyCode::Char('k'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_structured_diff())),
+            KeyEvent {
+                code: KeyCode::Char('s'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_layout())),
+            KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_hunk_only())),
+            KeyEvent {
+                code: KeyCode::Char('n'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.next_change())),
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.previous_change())),
+            KeyEvent {
+                code: KeyCode::Char('m'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.jump_to_move_pair())),
+            KeyEvent {
+                code: KeyCode::Char('M'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_color_moved())),
+            KeyEvent {
+                code: KeyCode::Char('h'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.scroll_column_left())),
+            KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.scroll_column_right())),
+            KeyEvent {
+                code: KeyCode::Char('W'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_wrap())),
+            KeyEvent {
+                code: KeyCode::Char('v'),
+                modifiers: _,
+            } => Ok(Control::Continue(state.toggle_show_whitespace())),
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: _,
+            } => match state.changed_file_location() {
+                Some(location) => Ok(Control::Open(state, location)),
+                None => Ok(Control::Continue(state)),
+            },
+            KeyEvent {
+                code: KeyCode::Backspace,
+                modifiers: _,
+            } => Ok(Control::Back(state)),
             KeyEvent {
                 code: KeyCode::Left,
                 modifiers: _,
-            } => Ok(Some(state.backward_commit(history))),
+            } => Ok(Control::Continue(state.backward_commit(history))),
             KeyEvent {
                 code: KeyCode::Right,
                 modifiers: _,
-            } => Ok(Some(state.forward_commit(history))),
+            } => Ok(Control::Continue(state.forward_commit(history))),
             KeyEvent {
                 code: KeyCode::Up,
                 modifiers: _,
-            } => Ok(Some(state.scroll_line_up())),
+            } => Ok(Control::Continue(state.scroll_line_up())),
             KeyEvent {
                 code: KeyCode::Down,
                 modifiers: _,
-            } => Ok(Some(state.scroll_line_down())),
+            } => Ok(Control::Continue(state.scroll_line_down())),
             KeyEvent {
                 code: KeyCode::PageUp,
                 modifiers: _,
-            } => Ok(Some(state.scroll_page_up())),
+            } => Ok(Control::Continue(state.scroll_page_up())),
             KeyEvent {
                 code: KeyCode::PageDown,
                 modifiers: _,
-            } => Ok(Some(state.scroll_page_down())),
+            } => Ok(Control::Continue(state.scroll_page_down())),
             KeyEvent {
                 code: KeyCode::Home,
                 modifiers: _,
-            } => Ok(Some(state.scroll_to_top())),
+            } => Ok(Control::Continue(state.scroll_to_top())),
             KeyEvent {
                 code: KeyCode::End,
                 modifiers: _,
-            } => Ok(Some(state.scroll_to_bottom())),
-            _ => Ok(Some(state)),
+            } => Ok(Control::Continue(state.scroll_to_bottom())),
+            _ => Ok(Control::Continue(state)),
         },
-        Event::Resize(_width, height) => {
-            Ok(Some(state.update_terminal_height(usize::from(height))))
-        }
-        _ => Ok(Some(state)),
+        Event::Resize(width, height) => Ok(Control::Continue(
+            state.update_terminal_size(usize::from(width), usize::from(height)),
+        )),
+        _ => Ok(Control::Continue(state)),
     }
 }
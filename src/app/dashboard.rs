@@ -1,39 +1,22 @@
-use crate::app::state::State;
+use crate::app::diff::{DiffLayout, DiffLine, DiffRowKind};
+use crate::app::image::ImageInfo;
+use crate::app::state::{Popup, State};
 use crate::app::terminal::Terminal;
-use crate::args::UserType;
+use crate::args::{DiffAlgorithm, Granularity, UserType, WhitespaceMode};
 use anyhow::Result;
-use once_cell::sync::Lazy;
-use std::iter;
+use similar::ChangeTag;
+use std::{cmp, convert::TryFrom, iter, mem};
 use tui::{layout, style, text, widgets};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-const COMMIT_INFO_INNER_HEIGHT: u16 = 2;
+const COMMIT_INFO_INNER_HEIGHT: u16 = 3;
 const COMMIT_INFO_OUTER_HEIGHT: u16 = COMMIT_INFO_INNER_HEIGHT + 2;
 const COMMIT_INFO_HORIZONTAL_PADDING: u16 = 1;
 const NAVI_WIDTH: u16 = 3;
-
-static BINARY_ALERT_TEXT: Lazy<Vec<text::Spans>> = Lazy::new(|| {
-    vec![
-        text::Spans::from(vec![text::Span::styled(
-            "╭──────────────────────────────────────────────╮",
-            style::Style::default().add_modifier(style::Modifier::DIM),
-        )]),
-        text::Spans::from(vec![
-            text::Span::styled(
-                "│",
-                style::Style::default().add_modifier(style::Modifier::DIM),
-            ),
-            text::Span::raw(" Note: binary data is not shown in a terminal "),
-            text::Span::styled(
-                "│",
-                style::Style::default().add_modifier(style::Modifier::DIM),
-            ),
-        ]),
-        text::Spans::from(vec![text::Span::styled(
-            "╰──────────────────────────────────────────────╯",
-            style::Style::default().add_modifier(style::Modifier::DIM),
-        )]),
-    ]
-});
+const POPUP_WIDTH_PERCENT: u16 = 80;
+const POPUP_HEIGHT_PERCENT: u16 = 70;
+const DETAILED_DATE_FORMAT: &str = "%a %b %e %H:%M:%S %Y %z";
+const SPLIT_LAYOUT_MIN_WIDTH: usize = 160;
 
 #[derive(Debug)]
 pub struct Dashboard<'a> {
@@ -41,7 +24,14 @@ pub struct Dashboard<'a> {
     commit_info_paragraph: widgets::Paragraph<'a>,
     left_navi_paragraph: widgets::Paragraph<'a>,
     right_navi_paragraph: widgets::Paragraph<'a>,
-    diff_paragraph: widgets::Paragraph<'a>,
+    diff_paragraphs: Vec<widgets::Paragraph<'a>>, // two paragraphs for the split layout
+    popup_widget: Option<PopupWidget<'a>>,
+}
+
+#[derive(Debug)]
+enum PopupWidget<'a> {
+    Paragraph(widgets::Paragraph<'a>),
+    List(widgets::List<'a>, widgets::ListState),
 }
 
 impl<'a> Dashboard<'a> {
@@ -51,7 +41,8 @@ impl<'a> Dashboard<'a> {
             commit_info_paragraph: Self::get_commit_info_paragraph(state),
             left_navi_paragraph: Self::get_left_navi_paragraph(state),
             right_navi_paragraph: Self::get_right_navi_paragraph(state),
-            diff_paragraph: Self::get_diff_paragraph(state),
+            diff_paragraphs: Self::get_diff_paragraphs(state),
+            popup_widget: Self::get_popup_widget(state),
         }
     }
 
@@ -112,7 +103,33 @@ impl<'a> Dashboard<'a> {
             frame.render_widget(self.commit_info_paragraph, commit_info_inner_chunk);
 
             // diff
-            frame.render_widget(self.diff_paragraph, diff_chunk);
+            let diff_chunks = layout::Layout::default()
+                .direction(layout::Direction::Horizontal)
+                .constraints(
+                    iter::repeat_n(
+                        layout::Constraint::Ratio(1, self.diff_paragraphs.len() as u32),
+                        self.diff_paragraphs.len(),
+                    )
+                    .collect::<Vec<_>>(),
+                )
+                .split(diff_chunk);
+            for (paragraph, chunk) in self.diff_paragraphs.into_iter().zip(diff_chunks) {
+                frame.render_widget(paragraph, chunk);
+            }
+
+            // popup
+            if let Some(popup_widget) = self.popup_widget {
+                let popup_chunk = Self::centered_rect(frame.size());
+                frame.render_widget(widgets::Clear, popup_chunk);
+                match popup_widget {
+                    PopupWidget::Paragraph(paragraph) => {
+                        frame.render_widget(paragraph, popup_chunk);
+                    }
+                    PopupWidget::List(list, mut list_state) => {
+                        frame.render_stateful_widget(list, popup_chunk, &mut list_state);
+                    }
+                }
+            }
         })?;
 
         Ok(())
@@ -122,21 +139,67 @@ impl<'a> Dashboard<'a> {
         terminal_height.saturating_sub(usize::from(COMMIT_INFO_OUTER_HEIGHT))
     }
 
+    pub fn can_split(terminal_width: usize) -> bool {
+        terminal_width >= SPLIT_LAYOUT_MIN_WIDTH
+    }
+
+    fn centered_rect(area: layout::Rect) -> layout::Rect {
+        let vertical_margin = (100 - POPUP_HEIGHT_PERCENT) / 2;
+        let horizontal_margin = (100 - POPUP_WIDTH_PERCENT) / 2;
+
+        let vertical_chunk = layout::Layout::default()
+            .direction(layout::Direction::Vertical)
+            .constraints(
+                [
+                    layout::Constraint::Percentage(vertical_margin),
+                    layout::Constraint::Percentage(POPUP_HEIGHT_PERCENT),
+                    layout::Constraint::Percentage(vertical_margin),
+                ]
+                .as_ref(),
+            )
+            .split(area)[1];
+
+        layout::Layout::default()
+            .direction(layout::Direction::Horizontal)
+            .constraints(
+                [
+                    layout::Constraint::Percentage(horizontal_margin),
+                    layout::Constraint::Percentage(POPUP_WIDTH_PERCENT),
+                    layout::Constraint::Percentage(horizontal_margin),
+                ]
+                .as_ref(),
+            )
+            .split(vertical_chunk)[1]
+    }
+
     fn get_left_navi_paragraph(state: &'a State) -> widgets::Paragraph<'a> {
-        let backward_symbol = if state.point().is_earliest() {
-            ""
+        // older commits are not fetched in a shallow clone
+        let backward_symbol = if !state.point().is_earliest() {
+            text::Span::raw("<<")
+        } else if state.point().diff().is_parent_missing() {
+            text::Span::styled("┄┄", style::Style::default().fg(style::Color::Yellow))
         } else {
-            "<<"
+            text::Span::raw("")
         };
         let up_symbol = if state.can_move_up() { "^" } else { "" };
         let down_symbol = if state.can_move_down() { "v" } else { "" };
 
-        widgets::Paragraph::new(vec![
-            text::Spans::from(format!("{:^1$}", up_symbol, usize::from(NAVI_WIDTH))),
-            text::Spans::from(format!("{:<1$}", backward_symbol, usize::from(NAVI_WIDTH))),
-            text::Spans::from(format!("{:<1$}", backward_symbol, usize::from(NAVI_WIDTH))),
-            text::Spans::from(format!("{:^1$}", down_symbol, usize::from(NAVI_WIDTH))),
-        ])
+        let mut navi_text = vec![];
+        navi_text.push(text::Spans::from(format!(
+            "{:^1$}",
+            up_symbol,
+            usize::from(NAVI_WIDTH)
+        )));
+        for _ in 0..COMMIT_INFO_INNER_HEIGHT {
+            navi_text.push(text::Spans::from(backward_symbol.clone()));
+        }
+        navi_text.push(text::Spans::from(format!(
+            "{:^1$}",
+            down_symbol,
+            usize::from(NAVI_WIDTH)
+        )));
+
+        widgets::Paragraph::new(navi_text)
     }
 
     fn get_right_navi_paragraph(state: &'a State) -> widgets::Paragraph<'a> {
@@ -144,12 +207,26 @@ impl<'a> Dashboard<'a> {
         let up_symbol = if state.can_move_up() { "^" } else { "" };
         let down_symbol = if state.can_move_down() { "v" } else { "" };
 
-        widgets::Paragraph::new(vec![
-            text::Spans::from(format!("{:^1$}", up_symbol, usize::from(NAVI_WIDTH))),
-            text::Spans::from(format!("{:>1$}", forward_symbol, usize::from(NAVI_WIDTH))),
-            text::Spans::from(format!("{:>1$}", forward_symbol, usize::from(NAVI_WIDTH))),
-            text::Spans::from(format!("{:^1$}", down_symbol, usize::from(NAVI_WIDTH))),
-        ])
+        let mut navi_text = vec![];
+        navi_text.push(text::Spans::from(format!(
+            "{:^1$}",
+            up_symbol,
+            usize::from(NAVI_WIDTH)
+        )));
+        for _ in 0..COMMIT_INFO_INNER_HEIGHT {
+            navi_text.push(text::Spans::from(format!(
+                "{:>1$}",
+                forward_symbol,
+                usize::from(NAVI_WIDTH)
+            )));
+        }
+        navi_text.push(text::Spans::from(format!(
+            "{:^1$}",
+            down_symbol,
+            usize::from(NAVI_WIDTH)
+        )));
+
+        widgets::Paragraph::new(navi_text)
     }
 
     fn get_commit_info_title(state: &'a State) -> text::Spans<'a> {
@@ -223,73 +300,847 @@ impl<'a> Dashboard<'a> {
     fn get_commit_info_paragraph(state: &'a State) -> widgets::Paragraph<'a> {
         let commit_summary =
             text::Spans::from(vec![text::Span::raw(state.point().commit().summary())]);
-        let change_status = text::Spans(vec![text::Span::raw(state.point().diff().status())]);
+        let change_status = Self::get_change_status_spans(state);
+        let containment = Self::get_containment_spans(state);
 
-        widgets::Paragraph::new(vec![commit_summary, change_status])
+        widgets::Paragraph::new(vec![commit_summary, change_status, containment])
     }
 
-    fn get_diff_paragraph(state: &'a State) -> widgets::Paragraph<'a> {
-        if let Some(lines) = state.point().diff().lines() {
-            let mut diff_text = vec![];
-            let max_line_number_len = state.max_line_number_len();
-            for line in lines.iter().skip(state.line_index()) {
-                let old_line_number = format!(
-                    "{:>1$}",
-                    if let Some(number) = line.old_line_number() {
-                        number.to_string()
-                    } else {
-                        String::new()
-                    },
-                    max_line_number_len,
+    fn get_change_status_spans(state: &'a State) -> text::Spans<'a> {
+        let mut spans = vec![text::Span::raw(state.diff().status())];
+
+        let folded_commits = state.point().folded_commits();
+        if !folded_commits.is_empty() && state.changed_file_index().is_none() {
+            spans.push(text::Span::styled(
+                format!(
+                    " (including {} skipped commit{})",
+                    folded_commits.len(),
+                    if folded_commits.len() == 1 { "" } else { "s" }
+                ),
+                style::Style::default().fg(style::Color::Yellow),
+            ));
+        }
+
+        let diff = state.diff();
+        if let (Some(old_encoding), Some(new_encoding)) = diff.encodings() {
+            let (old_id, new_id) = diff.blob_ids();
+            if !old_id.is_zero() && !new_id.is_zero() && old_encoding != new_encoding {
+                spans.push(text::Span::styled(
+                    format!(" (encoding: {} → {})", old_encoding, new_encoding),
+                    style::Style::default().fg(style::Color::Yellow),
+                ));
+            } else if !new_id.is_zero() && !new_encoding.is_default() {
+                spans.push(text::Span::raw(format!(" (encoding: {})", new_encoding)));
+            } else if new_id.is_zero() && !old_encoding.is_default() {
+                spans.push(text::Span::raw(format!(" (encoding: {})", old_encoding)));
+            }
+        }
+        if diff.has_lfs_pointer() {
+            spans.push(text::Span::styled(
+                " (LFS)",
+                style::Style::default().add_modifier(style::Modifier::DIM),
+            ));
+        }
+        if let Some(command) = diff.textconv() {
+            spans.push(text::Span::styled(
+                format!(" (textconv: {})", command),
+                style::Style::default().add_modifier(style::Modifier::DIM),
+            ));
+        }
+        if let Some((old_stats, new_stats)) = diff.line_ending_stats() {
+            let (old_id, new_id) = diff.blob_ids();
+            if !old_id.is_zero()
+                && !new_id.is_zero()
+                && old_stats.to_string() != new_stats.to_string()
+            {
+                spans.push(text::Span::styled(
+                    format!(" (line endings: {} → {})", old_stats, new_stats),
+                    style::Style::default().fg(style::Color::Yellow),
+                ));
+            } else {
+                let stats = if new_id.is_zero() {
+                    old_stats
+                } else {
+                    new_stats
+                };
+                if stats.has_cr() {
+                    spans.push(text::Span::raw(format!(" (line endings: {})", stats)));
+                }
+            }
+        }
+        if let Some(Err(message)) = diff
+            .data_changes()
+            .filter(|_| state.diff_options().structured())
+        {
+            spans.push(text::Span::styled(
+                format!(" (line diff: {})", message),
+                style::Style::default().fg(style::Color::Yellow),
+            ));
+        }
+        if diff.is_binary() {
+            let (old_size, new_size) = diff.blob_sizes();
+            let (old_id, new_id) = diff.blob_ids();
+            let short_id = |oid: git2::Oid| {
+                if oid.is_zero() {
+                    String::from("none")
+                } else {
+                    oid.to_string()[..7].to_string()
+                }
+            };
+            let (old_image, new_image) = diff.image_infos();
+            if old_image.is_some() || new_image.is_some() {
+                let describe = |info: &Option<ImageInfo>| {
+                    info.as_ref()
+                        .map_or_else(|| String::from("none"), |info| info.to_string())
+                };
+                spans.push(text::Span::styled(
+                    format!(
+                        " (image: {} → {})",
+                        describe(old_image),
+                        describe(new_image)
+                    ),
+                    style::Style::default().fg(style::Color::Cyan),
+                ));
+            }
+            spans.push(text::Span::raw(format!(
+                " (binary: {} → {} bytes, {} → {})",
+                old_size,
+                new_size,
+                short_id(old_id),
+                short_id(new_id)
+            )));
+            if cmp::max(old_size, new_size) > state.args().hex_limit {
+                spans.push(text::Span::styled(
+                    format!(" [first {} bytes]", state.args().hex_limit),
+                    style::Style::default().fg(style::Color::Yellow),
+                ));
+            }
+        }
+
+        let mut options = vec![];
+        let algorithm = state.diff_options().algorithm();
+        if algorithm != DiffAlgorithm::Myers {
+            options.push(algorithm.name());
+        }
+        let whitespace_mode = state.diff_options().whitespace_mode();
+        if whitespace_mode != WhitespaceMode::None {
+            options.push(whitespace_mode.name());
+        }
+        if state.diff_options().ignore_blank_lines() {
+            options.push("ignore-blank-lines");
+        }
+        let granularity = state.diff_options().granularity();
+        if granularity != Granularity::Word {
+            options.push(granularity.name());
+        }
+        if state.diff_options().word_diff() {
+            options.push("word-diff");
+        }
+        if state.diff_options().structured() {
+            options.push("structured");
+        }
+        if state.diff_options().color_moved() {
+            options.push("color-moved");
+        }
+        if state.diff_options().context_lines().is_some() {
+            options.push("hunk-only");
+        }
+        if !options.is_empty() {
+            spans.push(text::Span::styled(
+                format!(" [{}]", options.join(", ")),
+                style::Style::default().add_modifier(style::Modifier::DIM),
+            ));
+        }
+
+        text::Spans::from(spans)
+    }
+
+    fn get_containment_spans(state: &'a State) -> text::Spans<'a> {
+        let containment = state.point().commit().containment();
+        if containment.is_empty() {
+            return text::Spans::from(text::Span::styled(
+                "* Not contained in any tag or release branch yet",
+                style::Style::default().add_modifier(style::Modifier::DIM),
+            ));
+        }
+
+        let mut spans = vec![text::Span::raw("* Contained in: ")];
+        if let Some(name) = containment.earliest_tag_name() {
+            spans.push(text::Span::styled(
+                name,
+                style::Style::default().fg(style::Color::Yellow),
+            ));
+            spans.push(text::Span::raw(", "));
+        }
+        for name in containment.release_branch_names().iter() {
+            spans.push(text::Span::styled(
+                name.as_str(),
+                style::Style::default().fg(style::Color::Green),
+            ));
+            spans.push(text::Span::raw(", "));
+        }
+        spans.pop();
+
+        text::Spans::from(spans)
+    }
+
+    fn get_diff_paragraphs(state: &'a State) -> Vec<widgets::Paragraph<'a>> {
+        if let Some(reason) = state.diff().unavailable_reason() {
+            return vec![Self::get_alert_paragraph(state, reason)];
+        }
+        if let Some(Ok(changes)) = state
+            .diff()
+            .data_changes()
+            .filter(|_| state.diff_options().structured())
+        {
+            if changes.is_empty() {
+                return vec![Self::get_alert_paragraph(
+                    state,
+                    "No changes in the structure of the data",
+                )];
+            }
+        }
+
+        let rows = state.diff().rows(state.diff_options());
+        let diff_height = Self::diff_height(state.terminal_height());
+        let mut diff_text = vec![];
+        let mut new_diff_text = vec![];
+        for row in rows.iter().skip(state.line_index()).take(diff_height) {
+            let (mut old_text, mut new_text) = Self::get_row_text(state, row.kind());
+            diff_text.append(&mut old_text);
+            new_diff_text.append(&mut new_text);
+        }
+
+        let mut paragraphs = vec![widgets::Paragraph::new(diff_text)];
+        if state.diff_options().layout() == DiffLayout::Split {
+            paragraphs.push(widgets::Paragraph::new(new_diff_text));
+        }
+        paragraphs
+    }
+
+    fn get_alert_paragraph(state: &'a State, message: &str) -> widgets::Paragraph<'a> {
+        let message = format!(" Note: {} ", message);
+        let border = "─".repeat(message.width());
+        let border_style = style::Style::default().add_modifier(style::Modifier::DIM);
+        let alert_text = vec![
+            text::Spans::from(text::Span::styled(format!("╭{}╮", border), border_style)),
+            text::Spans::from(vec![
+                text::Span::styled("│", border_style),
+                text::Span::raw(message),
+                text::Span::styled("│", border_style),
+            ]),
+            text::Spans::from(text::Span::styled(format!("╰{}╯", border), border_style)),
+        ];
+
+        let diff_height = Self::diff_height(state.terminal_height());
+        let offset = diff_height.saturating_sub(alert_text.len()) / 2;
+        let text = iter::repeat_n(text::Spans::from(vec![]), offset)
+            .chain(alert_text)
+            .collect::<Vec<_>>();
+        widgets::Paragraph::new(text).alignment(layout::Alignment::Center)
+    }
+
+    // the number of lines of a row in the terminal
+    pub fn row_height(state: &'a State, row_kind: DiffRowKind) -> usize {
+        Self::get_row_text(state, row_kind).0.len()
+    }
+
+    // lines of a row for the left and right sides, where the right side is empty for the unified layout
+    fn get_row_text(
+        state: &'a State,
+        row_kind: DiffRowKind,
+    ) -> (Vec<text::Spans<'a>>, Vec<text::Spans<'a>>) {
+        let lines = state.diff().lines(state.diff_options());
+        match row_kind {
+            DiffRowKind::Line(index) => {
+                let line = &lines[index];
+                let line_numbers = [line.old_line_number(), line.new_line_number()];
+                (
+                    Self::get_diff_line_text(state, Some(line), &line_numbers, None),
+                    vec![],
+                )
+            }
+            DiffRowKind::Fold(start, end) => {
+                if state.diff_options().layout() == DiffLayout::Split {
+                    (
+                        vec![Self::get_fold_spans(state, start, end, 1)],
+                        vec![Self::get_fold_spans(state, start, end, 1)],
+                    )
+                } else {
+                    (vec![Self::get_fold_spans(state, start, end, 2)], vec![])
+                }
+            }
+            DiffRowKind::Pair(old_index, new_index) => {
+                let old_line = old_index.map(|index| &lines[index]);
+                let new_line = new_index.map(|index| &lines[index]);
+                let mut old_text = Self::get_diff_line_text(
+                    state,
+                    old_line,
+                    &[old_line.and_then(|line| line.old_line_number())],
+                    Some(ChangeTag::Insert),
                 );
-                let new_line_number = format!(
-                    "{:>1$}",
-                    if let Some(number) = line.new_line_number() {
-                        number.to_string()
-                    } else {
-                        String::new()
-                    },
-                    max_line_number_len,
+                let mut new_text = Self::get_diff_line_text(
+                    state,
+                    new_line,
+                    &[new_line.and_then(|line| line.new_line_number())],
+                    Some(ChangeTag::Delete),
                 );
-                let sign = line.sign();
-                let style = line.style();
 
-                let mut spans = vec![
-                    text::Span::raw(old_line_number),
+                // align wrapped lines of both sides
+                let height = cmp::max(old_text.len(), new_text.len());
+                let blank_spans = text::Spans::from(Self::get_gutter_spans(state, &[None], false));
+                old_text.resize(height, blank_spans.clone());
+                new_text.resize(height, blank_spans);
+                (old_text, new_text)
+            }
+        }
+    }
+
+    fn get_fold_spans(
+        state: &'a State,
+        start: usize,
+        end: usize,
+        line_number_count: usize,
+    ) -> text::Spans<'a> {
+        let len = end - start;
+        let mut spans = vec![];
+        for _ in 0..line_number_count {
+            spans.push(text::Span::raw(" ".repeat(state.max_line_number_len() + 1)));
+        }
+        spans.push(text::Span::raw("│"));
+        spans.push(text::Span::styled(
+            format!(
+                " ⋯ {} unchanged line{}",
+                len,
+                if len == 1 { "" } else { "s" }
+            ),
+            style::Style::default().add_modifier(style::Modifier::DIM),
+        ));
+        // a function containing the next lines as a hunk header of `git diff`
+        if let Some(context) = state.diff().function_context(state.diff_options(), end) {
+            spans.push(text::Span::styled(
+                format!("  {}", context),
+                style::Style::default().fg(style::Color::Cyan),
+            ));
+        }
+        text::Spans::from(spans)
+    }
+
+    fn get_gutter_spans(
+        state: &'a State,
+        line_numbers: &[Option<usize>],
+        is_continuation: bool,
+    ) -> Vec<text::Span<'a>> {
+        let max_line_number_len = state.max_line_number_len();
+        let mut spans = vec![];
+        for (i, number) in line_numbers.iter().enumerate() {
+            let number = if is_continuation {
+                // a marker of a wrapped line
+                if i + 1 == line_numbers.len() {
+                    String::from("↪")
+                } else {
+                    String::new()
+                }
+            } else if let Some(number) = number {
+                number.to_string()
+            } else {
+                String::new()
+            };
+            spans.push(text::Span::raw(format!(
+                "{:>1$}",
+                number, max_line_number_len
+            )));
+            spans.push(text::Span::raw(" "));
+        }
+        spans.push(text::Span::raw("│"));
+        spans
+    }
+
+    // `line` is `None` for a filler row of the split layout, and changed words of a word diff are
+    // hidden on the other side
+    fn get_diff_line_text(
+        state: &'a State,
+        line: Option<&'a DiffLine>,
+        line_numbers: &[Option<usize>],
+        hidden_word_tag: Option<ChangeTag>,
+    ) -> Vec<text::Spans<'a>> {
+        let mut spans = Self::get_gutter_spans(state, line_numbers, false);
+        let line = match line {
+            Some(line) => line,
+            None => return vec![text::Spans::from(spans)],
+        };
+
+        let sign = line.sign();
+        let style = line.style();
+        spans.push(text::Span::styled(sign, style));
+        spans.push(text::Span::styled(" ", style));
+
+        let mut content = line
+            .parts()
+            .iter()
+            .zip(line.display_texts(
+                state.args().tab_size,
+                state.should_show_whitespace(),
+                hidden_word_tag,
+            ))
+            .filter(|(part, _)| hidden_word_tag.is_none() || part.word_tag() != hidden_word_tag)
+            .map(|(part, text)| {
+                // changed bytes of a hex dump are always emphasized
+                let should_emphasize =
+                    state.args().should_emphasize_diff || state.diff().is_binary();
+                let style = line.part_style(part, should_emphasize);
+                text::Span::styled(text, style)
+            })
+            .collect::<Vec<_>>();
+        if let Some(pair_start) = line.move_pair_start().filter(|_| line.is_move_start()) {
+            let pair = &state.diff().lines(state.diff_options())[pair_start];
+            let marker = match (pair.new_line_number(), pair.old_line_number()) {
+                (Some(number), _) => format!(" ⇄ moved to line {}", number),
+                (None, Some(number)) => format!(" ⇄ moved from line {}", number),
+                (None, None) => String::new(),
+            };
+            content.push(text::Span::styled(
+                marker,
+                style.add_modifier(style::Modifier::DIM),
+            ));
+        }
+        if let Some((old_ending, new_ending)) = line.line_ending_change() {
+            content.push(text::Span::styled(
+                format!(" [{} → {}]", old_ending.name(), new_ending.name()),
+                style.add_modifier(style::Modifier::DIM),
+            ));
+        }
+
+        // hex dumps have no newlines
+        let no_newline_text = if !line.has_final_newline() && !state.diff().is_binary() {
+            let mut spans = Self::get_gutter_spans(state, &vec![None; line_numbers.len()], false);
+            spans.push(text::Span::styled(
+                "\\ No newline at end of file",
+                style::Style::default().add_modifier(style::Modifier::DIM),
+            ));
+            Some(text::Spans::from(spans))
+        } else {
+            None
+        };
+
+        if state.should_wrap() {
+            let mut text = vec![];
+            for (i, mut content) in wrap_spans(content, Self::text_width(state))
+                .into_iter()
+                .enumerate()
+            {
+                if i > 0 {
+                    spans = Self::get_gutter_spans(state, line_numbers, true);
+                    spans.push(text::Span::styled("  ", style));
+                }
+                spans.append(&mut content);
+                text.push(text::Spans::from(spans.clone()));
+            }
+            text.extend(no_newline_text);
+            text
+        } else {
+            spans.append(&mut skip_columns(content, state.column_index()));
+            iter::once(text::Spans::from(spans))
+                .chain(no_newline_text)
+                .collect()
+        }
+    }
+
+    // the width for the text of a line, excluding the gutter and the sign
+    pub fn text_width(state: &'a State) -> usize {
+        let line_number_width = state.max_line_number_len() + 1;
+        let width = match state.diff_options().layout() {
+            DiffLayout::Unified => state
+                .terminal_width()
+                .saturating_sub(line_number_width * 2 + 1),
+            DiffLayout::Split => (state.terminal_width() / 2).saturating_sub(line_number_width + 1),
+        };
+        cmp::max(width.saturating_sub(2), 1)
+    }
+
+    // the height inside the borders of the popup
+    pub fn popup_height(state: &'a State) -> usize {
+        usize::from(Self::popup_inner_rect(state).height)
+    }
+
+    // the largest index of the popup, i.e. a selected item of a list or the top line of a
+    // paragraph, counted in the wrapped lines rendered
+    pub fn popup_max_index(state: &'a State) -> usize {
+        let text = match state.popup() {
+            Some(Popup::TagMessage) => Self::get_tag_message_text(state),
+            Some(Popup::CommitDetails) => Self::get_commit_details_text(state),
+            Some(Popup::Error) => Self::get_error_text(state),
+            Some(Popup::ChangedFiles) => {
+                return state.point().changed_files().len().saturating_sub(1);
+            }
+            None => return 0,
+        };
+        let rect = Self::popup_inner_rect(state);
+        let height: usize = text
+            .iter()
+            .map(|spans| {
+                let line: String = spans.0.iter().map(|span| span.content.as_ref()).collect();
+                wrapped_height(&line, usize::from(rect.width))
+            })
+            .sum();
+        height.saturating_sub(usize::from(rect.height))
+    }
+
+    fn popup_inner_rect(state: &'a State) -> layout::Rect {
+        let area = layout::Rect::new(
+            0,
+            0,
+            u16::try_from(state.terminal_width()).unwrap_or(u16::MAX),
+            u16::try_from(state.terminal_height()).unwrap_or(u16::MAX),
+        );
+        Self::get_popup_block("").inner(Self::centered_rect(area))
+    }
+
+    fn get_popup_widget(state: &'a State) -> Option<PopupWidget<'a>> {
+        let (title, text) = match state.popup()? {
+            Popup::TagMessage => (" Tags ", Self::get_tag_message_text(state)),
+            Popup::CommitDetails => (" Commit ", Self::get_commit_details_text(state)),
+            Popup::Error => (" Error ", Self::get_error_text(state)),
+            Popup::ChangedFiles => {
+                let block = Self::get_popup_block(" Changed files ");
+                let list = widgets::List::new(Self::get_changed_file_items(state))
+                    .block(block)
+                    .highlight_style(
+                        style::Style::default().add_modifier(style::Modifier::REVERSED),
+                    );
+                let mut list_state = widgets::ListState::default();
+                list_state.select(Some(state.popup_line_index()));
+                return Some(PopupWidget::List(list, list_state));
+            }
+        };
+
+        Some(PopupWidget::Paragraph(
+            widgets::Paragraph::new(text)
+                .block(Self::get_popup_block(title))
+                .wrap(widgets::Wrap { trim: false })
+                .scroll((
+                    u16::try_from(state.popup_line_index()).unwrap_or(u16::MAX),
+                    0,
+                )),
+        ))
+    }
+
+    fn get_popup_block(title: &'a str) -> widgets::Block<'a> {
+        widgets::Block::default()
+            .title(title)
+            .borders(widgets::Borders::ALL)
+            .border_type(widgets::BorderType::Rounded)
+    }
+
+    fn get_changed_file_items(state: &'a State) -> Vec<widgets::ListItem<'a>> {
+        let changed_files = state.point().changed_files();
+        let stat_len = changed_files
+            .iter()
+            .filter_map(|diff| diff.line_stats())
+            .map(|stats| {
+                cmp::max(stats.additions(), stats.deletions())
+                    .to_string()
+                    .len()
+            })
+            .fold(0, cmp::max);
+
+        changed_files
+            .iter()
+            .enumerate()
+            .map(|(index, diff)| {
+                let is_shown = match state.changed_file_index() {
+                    Some(i) => i == index,
+                    None => diff.is_same_file(state.point().diff()),
+                };
+                let (additions, deletions) = match diff.line_stats() {
+                    Some(stats) => (
+                        format!("+{:<1$}", stats.additions(), stat_len),
+                        format!("-{:<1$}", stats.deletions(), stat_len),
+                    ),
+                    None => (
+                        format!("{:<1$}", "bin", stat_len + 1),
+                        " ".repeat(stat_len + 1),
+                    ),
+                };
+                widgets::ListItem::new(text::Spans::from(vec![
+                    text::Span::raw(if is_shown { "* " } else { "  " }),
+                    text::Span::styled(
+                        diff.status_symbol().to_string(),
+                        style::Style::default().fg(style::Color::Yellow),
+                    ),
                     text::Span::raw(" "),
-                    text::Span::raw(new_line_number),
-                    text::Span::raw(" │"),
-                    text::Span::styled(sign, style),
-                    text::Span::styled(" ", style),
-                ];
-                for part in line.parts().iter() {
-                    let style = if state.args().should_emphasize_diff {
-                        part.emphasize(style)
-                    } else {
-                        style
+                    text::Span::styled(additions, style::Style::default().fg(style::Color::Green)),
+                    text::Span::raw(" "),
+                    text::Span::styled(deletions, style::Style::default().fg(style::Color::Red)),
+                    text::Span::raw(" "),
+                    text::Span::raw(diff.path()),
+                ]))
+            })
+            .collect()
+    }
+
+    fn get_error_text(state: &'a State) -> Vec<text::Spans<'a>> {
+        state
+            .error_message()
+            .unwrap_or_default()
+            .lines()
+            .map(|line| {
+                text::Spans::from(text::Span::styled(
+                    line,
+                    style::Style::default().fg(style::Color::Red),
+                ))
+            })
+            .collect()
+    }
+
+    fn get_tag_message_text(state: &'a State) -> Vec<text::Spans<'a>> {
+        let tags = state.point().commit().references().tags();
+        if tags.is_empty() {
+            return vec![text::Spans::from(text::Span::styled(
+                "No tags point at this commit",
+                style::Style::default().add_modifier(style::Modifier::DIM),
+            ))];
+        }
+
+        let mut tag_text = vec![];
+        for tag in tags.iter() {
+            if !tag_text.is_empty() {
+                tag_text.push(text::Spans::from(vec![]));
+            }
+
+            let mut title = vec![
+                text::Span::raw("tag "),
+                text::Span::styled(tag.name(), style::Style::default().fg(style::Color::Yellow)),
+            ];
+
+            if let Some(annotation) = tag.annotation() {
+                tag_text.push(text::Spans::from(title));
+                if let Some(name) = annotation.tagger_name() {
+                    let tagger = match annotation.tagger_email() {
+                        Some(email) => format!("{} <{}>", name, email),
+                        None => name.to_string(),
                     };
-                    spans.push(text::Span::styled(part.text(), style));
+                    tag_text.push(text::Spans::from(vec![
+                        text::Span::raw("Tagger: "),
+                        text::Span::styled(tagger, style::Style::default().fg(style::Color::Cyan)),
+                    ]));
+                }
+                if let Some(date) = annotation.tagger_date() {
+                    tag_text.push(text::Spans::from(vec![
+                        text::Span::raw("Date:   "),
+                        text::Span::styled(
+                            date.format(&state.args().date_format).to_string(),
+                            style::Style::default().fg(style::Color::LightMagenta),
+                        ),
+                    ]));
                 }
+                tag_text.push(text::Spans::from(vec![]));
+                for line in annotation.message().lines() {
+                    tag_text.push(text::Spans::from(format!("    {}", line)));
+                }
+            } else {
+                title.push(text::Span::styled(
+                    " (lightweight)",
+                    style::Style::default().add_modifier(style::Modifier::DIM),
+                ));
+                tag_text.push(text::Spans::from(title));
+            }
+        }
 
-                let spans = text::Spans::from(spans);
+        tag_text
+    }
+
+    fn get_commit_details_text(state: &'a State) -> Vec<text::Spans<'a>> {
+        let commit = state.point().commit();
+        let label_style = style::Style::default().add_modifier(style::Modifier::DIM);
+        let user_style = style::Style::default().fg(style::Color::Cyan);
+        let date_style = style::Style::default().fg(style::Color::LightMagenta);
+
+        let mut details_text = vec![text::Spans::from(vec![
+            text::Span::styled("commit ", label_style),
+            text::Span::styled(
+                commit.long_id(),
+                style::Style::default().fg(style::Color::Yellow),
+            ),
+        ])];
+        for parent_id in commit.parent_ids().iter() {
+            details_text.push(text::Spans::from(vec![
+                text::Span::styled("Parent:     ", label_style),
+                text::Span::raw(parent_id.as_str()),
+            ]));
+        }
+        details_text.push(text::Spans::from(vec![
+            text::Span::styled("Author:     ", label_style),
+            text::Span::styled(
+                format!("{} <{}>", commit.author_name(), commit.author_email()),
+                user_style,
+            ),
+        ]));
+        details_text.push(text::Spans::from(vec![
+            text::Span::styled("AuthorDate: ", label_style),
+            text::Span::styled(
+                commit
+                    .author_date_with_offset()
+                    .format(DETAILED_DATE_FORMAT)
+                    .to_string(),
+                date_style,
+            ),
+        ]));
+        details_text.push(text::Spans::from(vec![
+            text::Span::styled("Commit:     ", label_style),
+            text::Span::styled(
+                format!("{} <{}>", commit.committer_name(), commit.committer_email()),
+                user_style,
+            ),
+        ]));
+        details_text.push(text::Spans::from(vec![
+            text::Span::styled("CommitDate: ", label_style),
+            text::Span::styled(
+                commit
+                    .committer_date_with_offset()
+                    .format(DETAILED_DATE_FORMAT)
+                    .to_string(),
+                date_style,
+            ),
+        ]));
+
+        details_text.push(text::Spans::from(vec![]));
+        for line in commit.message().lines() {
+            details_text.push(text::Spans::from(format!("    {}", line)));
+        }
 
-                diff_text.push(spans);
+        if !commit.trailers().is_empty() {
+            details_text.push(text::Spans::from(vec![]));
+            details_text.push(text::Spans::from(text::Span::styled(
+                "Trailers:",
+                label_style,
+            )));
+            for trailer in commit.trailers().iter() {
+                details_text.push(text::Spans::from(vec![
+                    text::Span::raw("    "),
+                    text::Span::styled(
+                        format!("{}:", trailer.key()),
+                        style::Style::default().fg(style::Color::Green),
+                    ),
+                    text::Span::raw(" "),
+                    text::Span::raw(trailer.value()),
+                ]));
             }
-            widgets::Paragraph::new(diff_text)
-        } else {
-            // for a binary file
-            let mut alert_text = vec![];
+        }
+
+        details_text
+    }
+}
+
+// Skip the leading columns of spans. A wide character across the edge is replaced with spaces.
+fn skip_columns(spans: Vec<text::Span<'_>>, columns: usize) -> Vec<text::Span<'_>> {
+    let mut skipped_width = 0;
+    spans
+        .into_iter()
+        .filter_map(|span| {
+            if skipped_width >= columns {
+                return Some(span);
+            }
+            let mut content = String::new();
+            for c in span.content.chars() {
+                let width = c.width().unwrap_or(0);
+                if skipped_width >= columns {
+                    content.push(c);
+                } else if skipped_width + width > columns {
+                    content.push_str(&" ".repeat(skipped_width + width - columns));
+                    skipped_width += width;
+                } else {
+                    skipped_width += width;
+                }
+            }
+            if content.is_empty() {
+                None
+            } else {
+                Some(text::Span::styled(content, span.style))
+            }
+        })
+        .collect()
+}
 
-            let diff_height = Self::diff_height(state.terminal_height());
-            let offset = diff_height.saturating_sub(BINARY_ALERT_TEXT.len()) / 2;
-            alert_text.append(
-                &mut iter::repeat(text::Spans::from(vec![]))
-                    .take(offset)
-                    .collect(),
-            );
-            alert_text.append(&mut BINARY_ALERT_TEXT.clone());
+// Split spans into lines of the width, without splitting a wide character.
+fn wrap_spans(spans: Vec<text::Span<'_>>, width: usize) -> Vec<Vec<text::Span<'_>>> {
+    let mut lines = vec![vec![]];
+    let mut line_width = 0;
+    for span in spans {
+        let mut content = String::new();
+        for c in span.content.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if line_width + char_width > width && line_width > 0 {
+                if !content.is_empty() {
+                    let content = mem::take(&mut content);
+                    lines
+                        .last_mut()
+                        .unwrap()
+                        .push(text::Span::styled(content, span.style));
+                }
+                lines.push(vec![]);
+                line_width = 0;
+            }
+            content.push(c);
+            line_width += char_width;
+        }
+        if !content.is_empty() {
+            lines
+                .last_mut()
+                .unwrap()
+                .push(text::Span::styled(content, span.style));
+        }
+    }
+    lines
+}
+
+// the number of lines into which `widgets::Wrap { trim: false }` of a paragraph wraps a line
+fn wrapped_height(line: &str, max_width: usize) -> usize {
+    if max_width == 0 {
+        return 0;
+    }
+    let char_width = |c: &char| c.width().unwrap_or(0);
+    let mut chars = line.chars().filter(|c| char_width(c) <= max_width);
+    let mut current_line = vec![];
+    let mut next_line = vec![];
+    let mut height = 0;
+    loop {
+        mem::swap(&mut current_line, &mut next_line);
+        next_line.clear();
+
+        let mut width: usize = current_line.iter().map(char_width).sum();
+        let mut chars_to_last_word_end = 0;
+        let mut prev_whitespace = false;
+        let mut chars_exhausted = true;
+        for c in &mut chars {
+            chars_exhausted = false;
+            let whitespace = c.is_whitespace() && c != '\u{a0}';
+            if whitespace && !prev_whitespace {
+                chars_to_last_word_end = current_line.len();
+            }
+            current_line.push(c);
+            width += char_width(&c);
+            if width > max_width {
+                // wrap at the last word end, or at the end of the line if there is none
+                let truncate_at = if chars_to_last_word_end != 0 {
+                    chars_to_last_word_end
+                } else {
+                    current_line.len() - 1
+                };
+                let remainder = &current_line[truncate_at..];
+                if let Some(i) = remainder.iter().position(|c| !c.is_whitespace()) {
+                    next_line.extend_from_slice(&remainder[i..]);
+                }
+                current_line.truncate(truncate_at);
+                break;
+            }
+            prev_whitespace = whitespace;
+        }
 
-            widgets::Paragraph::new(alert_text).alignment(layout::Alignment::Center)
+        if chars_exhausted && current_line.is_empty() {
+            break;
         }
+        height += 1;
     }
+    cmp::max(height, 1)
 }
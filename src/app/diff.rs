@@ -1,33 +1,78 @@
+use crate::app::attributes::{self, AttributeValue, DiffAttribute};
 use crate::app::dashboard::Dashboard;
+use crate::app::encoding::TextEncoding;
+use crate::app::git;
+use crate::app::highlight::{Highlighter, Token};
+use crate::app::image::ImageInfo;
+use crate::app::lfs::{self, LfsPointer};
 use crate::app::state::State;
-use crate::args::Args;
-use git2::{Delta, DiffDelta, Oid, Repository};
+use crate::app::structure::{self, DataChange, DataFormat};
+use crate::app::words;
+use crate::args::{Args, DiffAlgorithm, Granularity, WhitespaceMode};
+use elsa::FrozenMap;
+use git2::{Config, Delta, DiffDelta, FileMode, Oid, Repository};
 use once_cell::sync::OnceCell;
-use similar::{ChangeTag, TextDiff};
-use std::{cmp, ops::Deref};
+use regex::Regex;
+use similar::{Algorithm, ChangeTag, DiffOp, DiffTag, TextDiff};
+use std::{
+    borrow::Cow,
+    cmp,
+    collections::HashMap,
+    fmt, iter,
+    ops::{Deref, Range},
+    time::{Duration, Instant},
+};
 use tui::style::{Color, Style};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub struct Diff<'a> {
     status: Delta,
     old_file_oid: Oid,
     new_file_oid: Oid,
+    old_file_mode: FileMode,
+    new_file_mode: FileMode,
     old_path: Option<String>,
     new_path: Option<String>,
-    has_old_binary_file: bool,
-    has_new_binary_file: bool,
-    lines: OnceCell<Vec<DiffLine>>,
+    tree_id: Oid, // a tree of the commit to read .gitattributes
+    is_parent_missing: bool,
+    diff_attribute: OnceCell<DiffAttribute>,
+    lfs_pointers: OnceCell<(Option<LfsPointer>, Option<LfsPointer>)>,
+    unavailable_reason: OnceCell<Option<String>>,
+    is_forced_binary: OnceCell<Option<bool>>,
+    textconv: OnceCell<Option<String>>,
+    funcname_patterns: OnceCell<Vec<(bool, Regex)>>, // (is_negated, pattern)
+    word_regex: OnceCell<Option<Regex>>,
+    old_file_text: OnceCell<Vec<u8>>,
+    new_file_text: OnceCell<Vec<u8>>,
+    old_encoding: OnceCell<Option<TextEncoding>>, // `None` for binary files
+    new_encoding: OnceCell<Option<TextEncoding>>,
+    lines: FrozenMap<DiffOptions, Vec<DiffLine>>, // cache for each option
+    rows: FrozenMap<DiffOptions, Vec<DiffRow>>,
+    line_stats: Option<LineStats>,
+    line_ending_stats: OnceCell<Option<(LineEndingStats, LineEndingStats)>>,
+    image_infos: OnceCell<(Option<ImageInfo>, Option<ImageInfo>)>,
+    data_changes: OnceCell<Option<Result<Vec<DataChange>, String>>>,
     repo: &'a Repository,
+    highlighter: &'a Highlighter,
     args: &'a Args,
 }
 
 impl<'a> Diff<'a> {
-    pub fn new(diff_delta: &DiffDelta, repo: &'a Repository, args: &'a Args) -> Self {
+    pub fn new(
+        diff_delta: &DiffDelta,
+        tree_id: Oid,
+        repo: &'a Repository,
+        highlighter: &'a Highlighter,
+        args: &'a Args,
+    ) -> Self {
         let old_file_oid = diff_delta.old_file().id();
         let new_file_oid = diff_delta.new_file().id();
         Self {
             status: diff_delta.status(),
             old_file_oid,
             new_file_oid,
+            old_file_mode: diff_delta.old_file().mode(),
+            new_file_mode: diff_delta.new_file().mode(),
             old_path: diff_delta
                 .old_file()
                 .path()
@@ -36,200 +81,1879 @@ impl<'a> Diff<'a> {
                 .new_file()
                 .path()
                 .map(|p| p.to_string_lossy().to_string()),
-            has_old_binary_file: repo
-                .find_blob(old_file_oid)
-                .map(|blob| blob.is_binary())
-                .unwrap_or(false),
-            has_new_binary_file: repo
-                .find_blob(new_file_oid)
-                .map(|blob| blob.is_binary())
-                .unwrap_or(false),
-            lines: OnceCell::new(),
+            tree_id,
+            is_parent_missing: false,
+            diff_attribute: OnceCell::new(),
+            lfs_pointers: OnceCell::new(),
+            unavailable_reason: OnceCell::new(),
+            is_forced_binary: OnceCell::new(),
+            textconv: OnceCell::new(),
+            funcname_patterns: OnceCell::new(),
+            word_regex: OnceCell::new(),
+            old_file_text: OnceCell::new(),
+            new_file_text: OnceCell::new(),
+            old_encoding: OnceCell::new(),
+            new_encoding: OnceCell::new(),
+            lines: FrozenMap::new(),
+            rows: FrozenMap::new(),
+            line_stats: None,
+            line_ending_stats: OnceCell::new(),
+            image_infos: OnceCell::new(),
+            data_changes: OnceCell::new(),
             repo,
+            highlighter,
             args,
         }
     }
 
-    pub fn lines(&self) -> Option<&Vec<DiffLine>> {
-        if self.has_new_binary_file {
-            None
-        } else {
-            Some(self.lines.get_or_init(|| self.calc_lines()))
+    // for the boundary commit of a shallow clone, which is compared with an empty tree
+    pub fn with_parent_missing(mut self, is_parent_missing: bool) -> Self {
+        self.is_parent_missing = is_parent_missing;
+        self
+    }
+
+    pub fn is_parent_missing(&self) -> bool {
+        self.is_parent_missing
+    }
+
+    fn has_old_binary_file(&self) -> bool {
+        self.is_forced_binary()
+            .unwrap_or_else(|| self.old_encoding().is_none())
+    }
+
+    fn has_new_binary_file(&self) -> bool {
+        self.is_forced_binary()
+            .unwrap_or_else(|| self.new_encoding().is_none())
+    }
+
+    // `Some` if .gitattributes decides whether files are binary
+    fn is_forced_binary(&self) -> Option<bool> {
+        *self
+            .is_forced_binary
+            .get_or_init(|| match self.diff_attribute() {
+                DiffAttribute::Set => Some(false),
+                DiffAttribute::Unset => Some(true),
+                DiffAttribute::Driver(_) if self.textconv().is_some() => Some(false),
+                DiffAttribute::Driver(_) => self
+                    .driver_config("binary")
+                    .and_then(|value| Config::parse_bool(value).ok())
+                    .filter(|is_binary| *is_binary),
+                DiffAttribute::Unspecified => None,
+            })
+    }
+
+    fn diff_attribute(&self) -> &DiffAttribute {
+        self.diff_attribute.get_or_init(|| {
+            let path = self.new_path.as_deref().or(self.old_path.as_deref());
+            DiffAttribute::from(self.attribute_value(path, "diff"))
+        })
+    }
+
+    // All attributes are read from .gitattributes of the commit, even for the old file.
+    fn attribute_value(&self, path: Option<&str>, name: &str) -> AttributeValue {
+        match (path, self.repo.find_tree(self.tree_id)) {
+            (Some(path), Ok(tree)) => attributes::attribute_value(path, &tree, self.repo, name),
+            _ => AttributeValue::Unspecified,
+        }
+    }
+
+    // cf. `diff.<driver>.*` of git config
+    fn driver_config(&self, key: &str) -> Option<String> {
+        match self.diff_attribute() {
+            DiffAttribute::Driver(driver) => self
+                .repo
+                .config()
+                .ok()?
+                .get_string(&format!("diff.{}.{}", driver, key))
+                .ok(),
+            _ => None,
+        }
+    }
+
+    // a command to convert blobs to texts
+    pub fn textconv(&self) -> Option<&str> {
+        self.textconv
+            .get_or_init(|| self.driver_config("textconv"))
+            .as_deref()
+    }
+
+    fn old_encoding(&self) -> Option<TextEncoding> {
+        *self
+            .old_encoding
+            .get_or_init(|| self.detect_encoding(self.old_file_oid, self.old_path.as_deref()))
+    }
+
+    fn new_encoding(&self) -> Option<TextEncoding> {
+        *self
+            .new_encoding
+            .get_or_init(|| self.detect_encoding(self.new_file_oid, self.new_path.as_deref()))
+    }
+
+    // UTF-16 files are text even though their NULs make them look binary
+    fn detect_encoding(&self, oid: Oid, path: Option<&str>) -> Option<TextEncoding> {
+        match self.repo.find_blob(oid) {
+            Ok(blob) if LfsPointer::parse(blob.content()).is_some() => {
+                let content = self.find_blob_content(oid);
+                TextEncoding::detect(
+                    &content,
+                    lfs::looks_binary(&content),
+                    self.attribute_value(path, "working-tree-encoding").value(),
+                )
+            }
+            Ok(blob) => TextEncoding::detect(
+                blob.content(),
+                blob.is_binary(),
+                self.attribute_value(path, "working-tree-encoding").value(),
+            ),
+            Err(_) => Some(TextEncoding::default()),
+        }
+    }
+
+    // encodings of the old and new blobs, which are `None` for binary files
+    pub fn encodings(&self) -> (Option<TextEncoding>, Option<TextEncoding>) {
+        (self.old_encoding(), self.new_encoding())
+    }
+
+    // Git LFS pointers are resolved to objects in the local store
+    fn find_blob_content(&self, oid: Oid) -> Vec<u8> {
+        self.repo
+            .find_blob(oid)
+            .map(|blob| match LfsPointer::parse(blob.content()) {
+                Some(pointer) => pointer.load(self.repo).unwrap_or_default(),
+                None => blob.content().to_vec(),
+            })
+            .unwrap_or_default()
+    }
+
+    fn lfs_pointers(&self) -> &(Option<LfsPointer>, Option<LfsPointer>) {
+        self.lfs_pointers.get_or_init(|| {
+            let pointer = |oid| {
+                self.repo
+                    .find_blob(oid)
+                    .ok()
+                    .and_then(|blob| LfsPointer::parse(blob.content()))
+            };
+            (pointer(self.old_file_oid), pointer(self.new_file_oid))
+        })
+    }
+
+    pub fn has_lfs_pointer(&self) -> bool {
+        let (old_pointer, new_pointer) = self.lfs_pointers();
+        old_pointer.is_some() || new_pointer.is_some()
+    }
+
+    // a reason why contents of the diff cannot be shown
+    pub fn unavailable_reason(&self) -> Option<&str> {
+        self.unavailable_reason
+            .get_or_init(|| {
+                if self.is_parent_missing {
+                    return Some(String::from(
+                        "Parent commit not fetched in this shallow clone",
+                    ));
+                }
+                // gitlinks of submodules point at commits in other repositories
+                let is_missing = |oid: Oid, mode: FileMode| {
+                    !oid.is_zero()
+                        && mode != FileMode::Commit
+                        && !self.repo.odb().is_ok_and(|odb| odb.exists(oid))
+                };
+                if is_missing(self.old_file_oid, self.old_file_mode)
+                    || is_missing(self.new_file_oid, self.new_file_mode)
+                {
+                    return Some(String::from(if git::is_partial_clone(self.repo) {
+                        "Blob not fetched in this partial clone"
+                    } else {
+                        "Blob missing from the repository"
+                    }));
+                }
+
+                let (old_pointer, new_pointer) = self.lfs_pointers();
+                if old_pointer
+                    .iter()
+                    .chain(new_pointer)
+                    .any(|pointer| !pointer.is_downloaded(self.repo))
+                {
+                    Some(String::from("LFS object not downloaded"))
+                } else {
+                    None
+                }
+            })
+            .as_deref()
+    }
+
+    fn old_file_text(&self) -> &[u8] {
+        self.old_file_text
+            .get_or_init(|| self.file_text(self.old_file_oid, self.old_encoding()))
+    }
+
+    fn new_file_text(&self) -> &[u8] {
+        self.new_file_text
+            .get_or_init(|| self.file_text(self.new_file_oid, self.new_encoding()))
+    }
+
+    // contents converted by textconv, or decoded into UTF-8
+    fn file_text(&self, oid: Oid, encoding: Option<TextEncoding>) -> Vec<u8> {
+        let content = self.find_blob_content(oid);
+        if let Some(command) = self.textconv().filter(|_| !oid.is_zero()) {
+            // show the raw content if the command fails
+            if let Ok(text) = attributes::run_textconv(command, &content, self.repo) {
+                return text;
+            }
+        }
+        match encoding {
+            Some(encoding) => encoding.decode(&content).into_owned(),
+            None => content,
+        }
+    }
+
+    pub fn lines(&self, options: &DiffOptions) -> &[DiffLine] {
+        // lines do not depend on how they are shown
+        let options = &options
+            .clone()
+            .with_layout(DiffLayout::Unified)
+            .with_context_lines(None)
+            .with_expanded_folds(vec![]);
+        if let Some(lines) = self.lines.get(options) {
+            lines
+        } else {
+            self.lines.insert(options.clone(), self.calc_lines(options))
+        }
+    }
+
+    // rows shown in the terminal
+    pub fn rows(&self, options: &DiffOptions) -> &[DiffRow] {
+        let lines = self.lines(options);
+        if let Some(rows) = self.rows.get(options) {
+            rows
+        } else {
+            self.rows.insert(options.clone(), calc_rows(lines, options))
+        }
+    }
+
+    // the start of a fold shown at the row or expanded over it, and whether it is expanded
+    pub fn fold_at(&self, index: usize, options: &DiffOptions) -> Option<(usize, bool)> {
+        let line_index = match self.rows(options).get(index)?.kind {
+            DiffRowKind::Fold(start, _) => return Some((start, false)),
+            kind => kind.line_index()?,
+        };
+        let folded_options = options.clone().with_expanded_folds(vec![]);
+        self.rows(&folded_options)
+            .iter()
+            .find_map(|row| match row.kind {
+                DiffRowKind::Fold(start, end) if (start..end).contains(&line_index) => Some(start),
+                _ => None,
+            })
+            .filter(|start| options.expanded_folds.contains(start))
+            .map(|start| (start, true))
+    }
+
+    fn calc_lines(&self, options: &DiffOptions) -> Vec<DiffLine> {
+        if self.unavailable_reason().is_some() {
+            return vec![];
+        }
+        if self.is_binary() {
+            return self.calc_hex_lines(options);
+        }
+        if let Some(Ok(changes)) = self.data_changes().filter(|_| options.structured) {
+            return calc_structured_lines(changes);
+        }
+
+        let old_file_text = self.old_file_text();
+        let new_file_text = self.new_file_text();
+
+        // lines with CRs are compared by the slow path to show changes of line endings
+        let has_cr = old_file_text.contains(&b'\r') || new_file_text.contains(&b'\r');
+        if options.whitespace_mode == WhitespaceMode::None && !options.ignore_blank_lines && !has_cr
+        {
+            let text_diff = self
+                .configure_text_diff(options)
+                .diff_lines(old_file_text, new_file_text);
+            let lines = self.calc_inline_lines(&text_diff, 0, 0, options);
+            return self.finish_lines(lines, old_file_text, new_file_text, options);
+        }
+
+        // compare normalized lines, and then emphasize different parts in each changed block
+        let old_lines = split_lines(old_file_text);
+        let new_lines = split_lines(new_file_text);
+        let old_offsets = line_offsets(&old_lines);
+        let new_offsets = line_offsets(&new_lines);
+        let old_keys = old_lines
+            .iter()
+            .map(|line| normalize_line(line, options.whitespace_mode))
+            .collect::<Vec<_>>();
+        let new_keys = new_lines
+            .iter()
+            .map(|line| normalize_line(line, options.whitespace_mode))
+            .collect::<Vec<_>>();
+        let ops = similar::capture_diff_slices_deadline(
+            options.algorithm.into(),
+            &old_keys,
+            &new_keys,
+            self.args
+                .diff_timeout
+                .map(|timeout| Instant::now() + timeout),
+        );
+
+        let mut lines = vec![];
+        let mut ops = ops.iter().peekable();
+        while let Some(op) = ops.next() {
+            if let DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } = *op
+            {
+                // show new lines as `git diff -w` does
+                for i in 0..len {
+                    let old_line = old_lines[old_index + i];
+                    let new_line = new_lines[new_index + i];
+                    let mut line = DiffLine::new(
+                        Some(old_index + i),
+                        Some(new_index + i),
+                        ChangeTag::Equal,
+                        vec![self.new_line_part(new_line, false)],
+                    );
+                    if options.whitespace_mode == WhitespaceMode::None && old_line != new_line {
+                        line.line_ending_change =
+                            Some((LineEnding::of(old_line), LineEnding::of(new_line)));
+                    }
+                    lines.push(line);
+                }
+                continue;
+            }
+
+            let mut old_range = op.old_range();
+            let mut new_range = op.new_range();
+            while let Some(op) = ops.next_if(|op| !matches!(op, DiffOp::Equal { .. })) {
+                old_range.end = op.old_range().end;
+                new_range.end = op.new_range().end;
+            }
+
+            let is_blank = |line: &&[u8]| line.iter().all(|b| b.is_ascii_whitespace());
+            if options.ignore_blank_lines
+                && old_lines[old_range.clone()].iter().all(is_blank)
+                && new_lines[new_range.clone()].iter().all(is_blank)
+            {
+                for i in new_range {
+                    lines.push(DiffLine::new(
+                        None,
+                        Some(i),
+                        ChangeTag::Equal,
+                        vec![self.new_line_part(new_lines[i], false)],
+                    ));
+                }
+                continue;
+            }
+
+            let old_block =
+                &old_file_text[old_offsets[old_range.start]..old_offsets[old_range.end]];
+            let new_block =
+                &new_file_text[new_offsets[new_range.start]..new_offsets[new_range.end]];
+            let text_diff = self
+                .configure_text_diff(options)
+                .diff_lines(old_block, new_block);
+            lines.extend(self.calc_inline_lines(
+                &text_diff,
+                old_range.start,
+                new_range.start,
+                options,
+            ));
+        }
+
+        self.finish_lines(lines, old_file_text, new_file_text, options)
+    }
+
+    // set indices, detect moved lines, and layer syntax highlighting
+    fn finish_lines(
+        &self,
+        lines: Vec<DiffLine>,
+        old_file_text: &[u8],
+        new_file_text: &[u8],
+        options: &DiffOptions,
+    ) -> Vec<DiffLine> {
+        let old_tokens = if self.has_old_binary_file() {
+            None
+        } else {
+            self.highlighter.tokens(
+                self.old_file_oid,
+                self.old_path.as_deref(),
+                self.attribute_value(self.old_path.as_deref(), "linguist-language")
+                    .value(),
+                old_file_text,
+            )
+        };
+        let new_tokens = self.highlighter.tokens(
+            self.new_file_oid,
+            self.new_path.as_deref(),
+            self.attribute_value(self.new_path.as_deref(), "linguist-language")
+                .value(),
+            new_file_text,
+        );
+
+        let mut lines = lines
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut line)| {
+                line.index = index;
+
+                let old_line_tokens = old_tokens.zip(line.old_index).and_then(|(t, i)| t.get(i));
+                let new_line_tokens = new_tokens.zip(line.new_index).and_then(|(t, i)| t.get(i));
+                let line_tokens = if line.tag == ChangeTag::Delete {
+                    vec![old_line_tokens]
+                } else {
+                    vec![new_line_tokens, old_line_tokens]
+                };
+                // a line shown with another version of the text, e.g. with `--ignore-all-space`, does not match tokens
+                let text_len = line.parts.iter().map(|part| part.text.len()).sum::<usize>();
+                if let Some(tokens) = line_tokens.into_iter().flatten().find(|tokens| {
+                    tokens.iter().map(|token| token.text_len()).sum::<usize>() == text_len
+                }) {
+                    line.parts = highlight_parts(line.parts, tokens);
+                }
+                line
+            })
+            .collect::<Vec<_>>();
+        // detected once for each diff and option, since lines are cached
+        if options.color_moved {
+            detect_moves(&mut lines);
+        }
+        lines
+    }
+
+    // a hex dump of bytes aligned by a diff of bytes
+    fn calc_hex_lines(&self, options: &DiffOptions) -> Vec<DiffLine> {
+        let old_content = self.find_blob_content(self.old_file_oid);
+        let new_content = self.find_blob_content(self.new_file_oid);
+        let old_bytes = &old_content[..cmp::min(old_content.len(), self.args.hex_limit)];
+        let new_bytes = &new_content[..cmp::min(new_content.len(), self.args.hex_limit)];
+        let ops = similar::capture_diff_slices_deadline(
+            options.algorithm.into(),
+            old_bytes,
+            new_bytes,
+            // a diff of bytes is much longer than lines, so it always gives up at some point
+            Some(Instant::now() + self.args.diff_timeout.unwrap_or(HEX_DIFF_TIMEOUT)),
+        );
+
+        let mut is_old_changed = vec![true; old_bytes.len()];
+        let mut is_new_changed = vec![true; new_bytes.len()];
+        for op in ops.iter().filter(|op| matches!(op, DiffOp::Equal { .. })) {
+            is_old_changed[op.old_range()].fill(false);
+            is_new_changed[op.new_range()].fill(false);
+        }
+
+        // merge changes with short unchanged bytes between them
+        let mut regions: Vec<HexRegion> = vec![];
+        for op in ops.iter() {
+            let is_changed = !matches!(op, DiffOp::Equal { .. });
+            match regions.as_mut_slice() {
+                [.., last] if last.is_changed && is_changed => last.extend(op),
+                [.., changed, unchanged]
+                    if is_changed
+                        && changed.is_changed
+                        && unchanged.old_range.len() < HEX_BYTES_PER_LINE =>
+                {
+                    let unchanged = regions.pop().unwrap();
+                    let changed = regions.last_mut().unwrap();
+                    changed.old_range.end = unchanged.old_range.end;
+                    changed.new_range.end = unchanged.new_range.end;
+                    changed.extend(op);
+                }
+                _ => regions.push(HexRegion {
+                    old_range: op.old_range(),
+                    new_range: op.new_range(),
+                    is_changed,
+                }),
+            }
+        }
+
+        let mut lines = vec![];
+        let mut old_index = 0;
+        let mut new_index = 0;
+        for region in regions {
+            if !region.is_changed {
+                for Range { start: offset, end } in hex_line_ranges(region.new_range) {
+                    lines.push(DiffLine::new(
+                        Some(old_index),
+                        Some(new_index),
+                        ChangeTag::Equal,
+                        hex_line_parts(
+                            offset,
+                            &new_bytes[offset..end],
+                            &is_new_changed[offset..end],
+                        ),
+                    ));
+                    old_index += 1;
+                    new_index += 1;
+                }
+                continue;
+            }
+
+            for Range { start: offset, end } in hex_line_ranges(region.old_range) {
+                lines.push(DiffLine::new(
+                    Some(old_index),
+                    None,
+                    ChangeTag::Delete,
+                    hex_line_parts(
+                        offset,
+                        &old_bytes[offset..end],
+                        &is_old_changed[offset..end],
+                    ),
+                ));
+                old_index += 1;
+            }
+            for Range { start: offset, end } in hex_line_ranges(region.new_range) {
+                lines.push(DiffLine::new(
+                    None,
+                    Some(new_index),
+                    ChangeTag::Insert,
+                    hex_line_parts(
+                        offset,
+                        &new_bytes[offset..end],
+                        &is_new_changed[offset..end],
+                    ),
+                ));
+                new_index += 1;
+            }
+        }
+
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut line)| {
+                line.index = index;
+                line
+            })
+            .collect()
+    }
+
+    fn configure_text_diff(&self, options: &DiffOptions) -> similar::TextDiffConfig {
+        let mut config = TextDiff::configure();
+        config.algorithm(options.algorithm.into());
+        if let Some(timeout) = self.args.diff_timeout {
+            config.timeout(timeout);
+        }
+        config
+    }
+
+    fn calc_inline_lines(
+        &self,
+        text_diff: &TextDiff<[u8]>,
+        old_offset: usize,
+        new_offset: usize,
+        options: &DiffOptions,
+    ) -> Vec<DiffLine> {
+        let old_lines = text_diff.old_slices();
+        let new_lines = text_diff.new_slices();
+        let mut lines = vec![];
+        for op in text_diff.ops() {
+            let old_range = op.old_range();
+            let new_range = op.new_range();
+            if op.tag() == DiffTag::Equal {
+                lines.extend(old_range.zip(new_range).map(|(i, j)| {
+                    DiffLine::new(
+                        Some(i + old_offset),
+                        Some(j + new_offset),
+                        ChangeTag::Equal,
+                        vec![self.new_line_part(old_lines[i], false)],
+                    )
+                }));
+                continue;
+            }
+
+            let old_texts = old_lines[old_range.clone()]
+                .iter()
+                .map(|line| String::from_utf8_lossy(line))
+                .collect::<Vec<_>>();
+            let new_texts = new_lines[new_range.clone()]
+                .iter()
+                .map(|line| String::from_utf8_lossy(line))
+                .collect::<Vec<_>>();
+            let old_words = old_texts
+                .iter()
+                .map(|text| self.split_words(text, options))
+                .collect::<Vec<_>>();
+            let new_words = new_texts
+                .iter()
+                .map(|text| self.split_words(text, options))
+                .collect::<Vec<_>>();
+            let old_start = old_range.start + old_offset;
+            let new_start = new_range.start + new_offset;
+            if options.word_diff {
+                lines.extend(
+                    self.calc_word_diff_lines(
+                        &old_words, &new_words, old_start, new_start, options,
+                    ),
+                );
+            } else {
+                lines.extend(
+                    self.calc_emphasized_lines(
+                        &old_words, &new_words, old_start, new_start, options,
+                    ),
+                );
+            }
+        }
+        lines
+    }
+
+    // deleted lines and inserted lines whose different words are emphasized
+    fn calc_emphasized_lines(
+        &self,
+        old_words: &[Vec<&str>],
+        new_words: &[Vec<&str>],
+        old_start: usize,
+        new_start: usize,
+        options: &DiffOptions,
+    ) -> Vec<DiffLine> {
+        let old_flat = old_words.concat();
+        let new_flat = new_words.concat();
+        // only replaced lines are emphasized
+        let mut is_old_changed = vec![false; old_flat.len()];
+        let mut is_new_changed = vec![false; new_flat.len()];
+        if !old_flat.is_empty() && !new_flat.is_empty() {
+            is_old_changed.fill(true);
+            is_new_changed.fill(true);
+            for op in self.diff_words(&old_flat, &new_flat, options) {
+                if let DiffOp::Equal { .. } = op {
+                    is_old_changed[op.old_range()].fill(false);
+                    is_new_changed[op.new_range()].fill(false);
+                }
+            }
+        }
+
+        let to_lines = |words: &[Vec<&str>], is_changed: &[bool], start: usize, tag| {
+            let mut offset = 0;
+            words
+                .iter()
+                .enumerate()
+                .map(|(i, words)| {
+                    let mut parts: Vec<DiffLinePart> = vec![];
+                    for (word, emphasized) in words.iter().zip(&is_changed[offset..]) {
+                        match parts.last_mut() {
+                            Some(part) if part.emphasized == *emphasized => {
+                                part.text.push_str(word)
+                            }
+                            _ => parts.push(DiffLinePart::new(*word, *emphasized)),
+                        }
+                    }
+                    offset += words.len();
+                    let index = Some(start + i);
+                    match tag {
+                        ChangeTag::Delete => DiffLine::new(index, None, tag, parts),
+                        _ => DiffLine::new(None, index, tag, parts),
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut lines = to_lines(old_words, &is_old_changed, old_start, ChangeTag::Delete);
+        lines.extend(to_lines(
+            new_words,
+            &is_new_changed,
+            new_start,
+            ChangeTag::Insert,
+        ));
+        lines
+    }
+
+    // lines with changed words shown inline as `[-old-]{+new+}`, cf. `git diff --word-diff`
+    fn calc_word_diff_lines(
+        &self,
+        old_words: &[Vec<&str>],
+        new_words: &[Vec<&str>],
+        old_start: usize,
+        new_start: usize,
+        options: &DiffOptions,
+    ) -> Vec<DiffLine> {
+        let line_endings = |words: &[Vec<&str>]| {
+            words
+                .iter()
+                .flat_map(|words| {
+                    let len = words.len();
+                    let has_line_ending = words
+                        .last()
+                        .is_some_and(|word| word.ends_with(&['\r', '\n'][..]));
+                    (0..len).map(move |i| has_line_ending && i + 1 == len)
+                })
+                .collect::<Vec<_>>()
+        };
+        let old_flat = old_words.concat();
+        let new_flat = new_words.concat();
+        let is_old_line_ending = line_endings(old_words);
+        let is_new_line_ending = line_endings(new_words);
+
+        let mut builder = WordDiffBuilder::new(old_start, new_start);
+        for op in self.diff_words(&old_flat, &new_flat, options) {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            match tag {
+                DiffTag::Equal => {
+                    for i in new_range {
+                        builder.push(new_flat[i], ChangeTag::Equal, is_new_line_ending[i]);
+                    }
+                }
+                _ => {
+                    for i in old_range {
+                        builder.push(old_flat[i], ChangeTag::Delete, is_old_line_ending[i]);
+                    }
+                    for i in new_range {
+                        builder.push(new_flat[i], ChangeTag::Insert, is_new_line_ending[i]);
+                    }
+                }
+            }
+        }
+        builder.finish()
+    }
+
+    fn diff_words(
+        &self,
+        old_words: &[&str],
+        new_words: &[&str],
+        options: &DiffOptions,
+    ) -> Vec<DiffOp> {
+        similar::capture_diff_slices_deadline(
+            options.algorithm.into(),
+            old_words,
+            new_words,
+            self.args
+                .diff_timeout
+                .map(|timeout| Instant::now() + timeout),
+        )
+    }
+
+    // cf. `diff.<driver>.wordRegex` of git config
+    fn split_words<'t>(&self, line: &'t str, options: &DiffOptions) -> Vec<&'t str> {
+        let word_regex = self.word_regex.get_or_init(|| {
+            self.driver_config("wordRegex")
+                .and_then(|pattern| Regex::new(&pattern).ok())
+        });
+        words::split_words(line, options.granularity, word_regex.as_ref())
+    }
+
+    fn new_line_part(&self, line: &[u8], emphasized: bool) -> DiffLinePart {
+        DiffLinePart::new(String::from_utf8_lossy(line), emphasized)
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.has_old_binary_file() || self.has_new_binary_file()
+    }
+
+    // sizes of the old and new blobs in bytes
+    pub fn blob_sizes(&self) -> (usize, usize) {
+        let size = |oid| {
+            self.repo.find_blob(oid).map_or(0, |blob| {
+                LfsPointer::parse(blob.content()).map_or(blob.size(), |pointer| pointer.size())
+            })
+        };
+        (size(self.old_file_oid), size(self.new_file_oid))
+    }
+
+    pub fn blob_ids(&self) -> (Oid, Oid) {
+        (self.old_file_oid, self.new_file_oid)
+    }
+
+    // `None` for blobs that are not images
+    pub fn image_infos(&self) -> &(Option<ImageInfo>, Option<ImageInfo>) {
+        self.image_infos.get_or_init(|| {
+            let info = |oid| ImageInfo::parse(&self.find_blob_content(oid));
+            (info(self.old_file_oid), info(self.new_file_oid))
+        })
+    }
+
+    // Lines are key paths of a structured diff, which have no line numbers to keep positions.
+    pub fn is_structured(&self, options: &DiffOptions) -> bool {
+        options.structured && matches!(self.data_changes(), Some(Ok(_)))
+    }
+
+    // Key paths changed in a data file such as JSON, YAML, and TOML, or a message if a version
+    // fails to parse. `None` for other files.
+    pub fn data_changes(&self) -> Option<&Result<Vec<DataChange>, String>> {
+        self.data_changes
+            .get_or_init(|| {
+                if !matches!(self.status, Delta::Modified | Delta::Renamed)
+                    || self.is_binary()
+                    || self.unavailable_reason().is_some()
+                {
+                    return None;
+                }
+                let format = self.new_path.as_deref().and_then(DataFormat::of)?;
+                let parse = |text: &[u8], version| {
+                    format.parse(&String::from_utf8_lossy(text)).map_err(|_| {
+                        format!("invalid {} in the {} version", format.name(), version)
+                    })
+                };
+                Some(parse(self.old_file_text(), "old").and_then(|old_value| {
+                    let new_value = parse(self.new_file_text(), "new")?;
+                    Ok(structure::diff_values(&old_value, &new_value))
+                }))
+            })
+            .as_ref()
+    }
+
+    // `None` for binary files
+    pub fn line_ending_stats(&self) -> Option<&(LineEndingStats, LineEndingStats)> {
+        self.line_ending_stats
+            .get_or_init(|| {
+                if self.is_binary() || self.unavailable_reason().is_some() {
+                    None
+                } else {
+                    Some((
+                        LineEndingStats::new(self.old_file_text()),
+                        LineEndingStats::new(self.new_file_text()),
+                    ))
+                }
+            })
+            .as_ref()
+    }
+
+    // set by `git::get_changed_files`, `None` for binary files
+    pub fn with_line_stats(mut self, line_stats: Option<LineStats>) -> Self {
+        self.line_stats = line_stats;
+        self
+    }
+
+    pub fn line_stats(&self) -> Option<&LineStats> {
+        self.line_stats.as_ref()
+    }
+
+    pub fn is_whitespace_only(&self) -> bool {
+        if !matches!(self.status, Delta::Modified | Delta::Renamed)
+            || self.is_binary()
+            || self.unavailable_reason().is_some()
+        {
+            return false;
+        }
+
+        let normalize = |text: &[u8]| {
+            split_lines(text)
+                .into_iter()
+                .map(|line| normalize_line(line, WhitespaceMode::IgnoreAllSpace).into_owned())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+        };
+        normalize(self.old_file_text()) == normalize(self.new_file_text())
+    }
+
+    // merge changes of the older diff into this diff
+    pub fn fold_older(&mut self, older: Diff) {
+        self.old_file_oid = older.old_file_oid;
+        self.old_file_mode = older.old_file_mode;
+        self.old_path = older.old_path;
+        self.old_encoding = older.old_encoding;
+        self.is_parent_missing = older.is_parent_missing;
+        self.update_status();
+    }
+
+    // merge changes of the newer diff into this diff
+    pub fn fold_newer(&mut self, newer: Diff) {
+        self.new_file_oid = newer.new_file_oid;
+        self.new_file_mode = newer.new_file_mode;
+        self.new_path = newer.new_path;
+        self.tree_id = newer.tree_id;
+        self.new_encoding = newer.new_encoding;
+        self.update_status();
+    }
+
+    fn update_status(&mut self) {
+        self.status = if self.old_file_oid.is_zero() {
+            Delta::Added
+        } else if self.new_file_oid.is_zero() {
+            Delta::Deleted
+        } else if self.old_path != self.new_path {
+            Delta::Renamed
+        } else {
+            Delta::Modified
+        };
+        self.diff_attribute = OnceCell::new();
+        self.lfs_pointers = OnceCell::new();
+        self.unavailable_reason = OnceCell::new();
+        self.is_forced_binary = OnceCell::new();
+        self.textconv = OnceCell::new();
+        self.funcname_patterns = OnceCell::new();
+        self.word_regex = OnceCell::new();
+        self.old_file_text = OnceCell::new();
+        self.new_file_text = OnceCell::new();
+        self.lines = FrozenMap::new();
+        self.rows = FrozenMap::new();
+        self.line_stats = None;
+        self.line_ending_stats = OnceCell::new();
+        self.image_infos = OnceCell::new();
+        self.data_changes = OnceCell::new();
+    }
+
+    pub fn is_same_file(&self, other: &Diff) -> bool {
+        self.new_file_oid == other.new_file_oid && self.new_path == other.new_path
+    }
+
+    pub fn new_path(&self) -> Option<&str> {
+        self.new_path.as_deref()
+    }
+
+    pub fn path(&self) -> String {
+        match self.status {
+            Delta::Renamed => format!(
+                "{} -> {}",
+                self.old_path.as_deref().unwrap_or_default(),
+                self.new_path.as_deref().unwrap_or_default()
+            ),
+            _ => self.new_path.clone().unwrap_or_default(),
+        }
+    }
+
+    pub fn status_symbol(&self) -> char {
+        match self.status {
+            Delta::Modified => 'M',
+            Delta::Added => 'A',
+            Delta::Deleted => 'D',
+            Delta::Renamed => 'R',
+            Delta::Copied => 'C',
+            Delta::Typechange => 'T',
+            _ => '?',
+        }
+    }
+
+    pub fn status(&self) -> String {
+        match self.status {
+            Delta::Modified => format!("* Modified: {}", self.new_path.as_deref().unwrap()),
+            Delta::Added => format!("* Added: {}", self.new_path.as_deref().unwrap()),
+            Delta::Deleted => format!("* Deleted: {}", self.new_path.as_deref().unwrap()),
+            Delta::Renamed => format!(
+                "* Renamed: {} -> {}",
+                self.old_path.as_deref().unwrap(),
+                self.new_path.as_deref().unwrap()
+            ),
+            Delta::Copied => format!(
+                "* Copied: {} -> {}",
+                self.old_path.as_deref().unwrap_or_default(),
+                self.new_path.as_deref().unwrap_or_default()
+            ),
+            Delta::Typechange => format!(
+                "* Type changed: {}",
+                self.new_path.as_deref().unwrap_or_default()
+            ),
+            _ => format!(
+                "* Changed: {}",
+                self.new_path.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+
+    pub fn max_line_number_len(&self, options: &DiffOptions) -> usize {
+        self.lines(options)
+            .iter()
+            .filter_map(|change| {
+                cmp::max(change.old_index, change.new_index).map(|x| {
+                    // 0-indexed to 1-indexed
+                    x + 1
+                })
+            })
+            .fold(0, cmp::max)
+            .to_string()
+            .len()
+    }
+
+    // The nearest old line above a line that looks like a function header, which is shown as
+    // a hunk header of `git diff`. cf. `diff.<driver>.xfuncname` of git config
+    pub fn function_context(&self, options: &DiffOptions, index: usize) -> Option<String> {
+        if self.is_binary() {
+            return None;
+        }
+        let patterns = self.funcname_patterns.get_or_init(|| {
+            let patterns = self
+                .driver_config("xfuncname")
+                .or_else(|| self.driver_config("funcname"))
+                .unwrap_or_else(|| String::from("^[[:alpha:]_$].*$"));
+            patterns
+                .lines()
+                .filter_map(|pattern| match pattern.strip_prefix('!') {
+                    Some(pattern) => Regex::new(pattern).ok().map(|regex| (true, regex)),
+                    None => Regex::new(pattern).ok().map(|regex| (false, regex)),
+                })
+                .collect()
+        });
+
+        self.lines(options)[..index]
+            .iter()
+            .rev()
+            .filter(|line| line.old_index.is_some())
+            .find_map(|line| {
+                let text = line
+                    .parts
+                    .iter()
+                    .map(|part| part.text.as_str())
+                    .collect::<String>();
+                let text = text.trim_end();
+                // the first matched pattern decides it
+                let (is_negated, regex) =
+                    patterns.iter().find(|(_, regex)| regex.is_match(text))?;
+                if *is_negated {
+                    return None;
+                }
+                let captures = regex.captures(text)?;
+                let context = captures.get(1).or_else(|| captures.get(0))?;
+                Some(context.as_str().trim_end().to_string())
+            })
+            .filter(|context| !context.is_empty())
+    }
+
+    // a row of the other end of the first moved block from the row
+    pub fn move_pair_row_index(&self, options: &DiffOptions, row_index: usize) -> Option<usize> {
+        let lines = self.lines(options);
+        let rows = self.rows(options);
+        let line_indices = |row: &DiffRow| match row.kind {
+            DiffRowKind::Line(index) => vec![index],
+            DiffRowKind::Pair(old_index, new_index) => {
+                old_index.into_iter().chain(new_index).collect()
+            }
+            DiffRowKind::Fold(_, _) => vec![],
+        };
+        let pair_start = rows
+            .iter()
+            .skip(row_index)
+            .flat_map(line_indices)
+            .find_map(|index| lines[index].move_pair_start())?;
+        rows.iter()
+            .position(|row| line_indices(row).contains(&pair_start))
+    }
+
+    // indices of rows where runs of changed lines start
+    pub fn change_start_indices(&self, options: &DiffOptions) -> Vec<usize> {
+        let lines = self.lines(options);
+        let rows = self.rows(options);
+        // each key path is a change
+        if self.is_structured(options) {
+            return (0..rows.len()).collect();
+        }
+        let is_changed = |row: &DiffRow| match row.kind {
+            DiffRowKind::Line(index) => lines[index].is_changed(),
+            DiffRowKind::Pair(old_index, new_index) => old_index
+                .into_iter()
+                .chain(new_index)
+                .any(|index| lines[index].is_changed()),
+            DiffRowKind::Fold(_, _) => false,
+        };
+        rows.iter()
+            .enumerate()
+            .filter(|(i, row)| is_changed(row) && (*i == 0 || !is_changed(&rows[i - 1])))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // the maximum display width of lines
+    pub fn max_text_width(&self, options: &DiffOptions) -> usize {
+        self.lines(options)
+            .iter()
+            .map(|line| {
+                line.display_texts(self.args.tab_size, false, None)
+                    .iter()
+                    .map(|text| UnicodeWidthStr::width(text.as_str()))
+                    .sum::<usize>()
+            })
+            .fold(0, cmp::max)
+    }
+
+    pub fn allowed_min_index(&self, _state: &State) -> usize {
+        0
+    }
+
+    pub fn allowed_max_index(&self, state: &State) -> usize {
+        let rows = self.rows(state.diff_options());
+        let diff_length = rows.len();
+        let diff_height = Dashboard::diff_height(state.terminal_height());
+
+        if state.args().beyond_last_line {
+            diff_length.saturating_sub(1)
+        } else {
+            // The first row such that the following rows fit in the view, where a row takes more
+            // than one line if it is wrapped or followed by "\ No newline at end of file".
+            let mut index = diff_length;
+            let mut height = 0;
+            while index > 0 {
+                height += Dashboard::row_height(state, rows[index - 1].kind);
+                if height > diff_height {
+                    break;
+                }
+                index -= 1;
+            }
+            cmp::min(index, diff_length.saturating_sub(1))
+        }
+    }
+
+    pub fn can_move_up(&self, index: usize, state: &State) -> bool {
+        index > self.allowed_min_index(state)
+    }
+
+    pub fn can_move_down(&self, index: usize, state: &State) -> bool {
+        index < self.allowed_max_index(state)
+    }
+
+    pub fn nearest_old_index_pair(&self, index: usize, options: &DiffOptions) -> IndexPair {
+        let rows = self.rows(options);
+        if let Some(row) = rows.iter().skip(index).find(|row| row.old_index.is_some()) {
+            assert!(row.index >= index);
+            IndexPair::new(row.index - index, row.old_index.unwrap())
+        } else if let Some(row) = rows
+            .iter()
+            .take(index)
+            .rev()
+            .find(|row| row.old_index.is_some())
+        {
+            assert!(row.index < index);
+            IndexPair::new(0, row.old_index.unwrap())
+        } else {
+            IndexPair::new(0, 0)
+        }
+    }
+
+    pub fn nearest_new_index_pair(&self, index: usize, options: &DiffOptions) -> IndexPair {
+        let rows = self.rows(options);
+        if let Some(row) = rows.iter().skip(index).find(|row| row.new_index.is_some()) {
+            assert!(row.index >= index);
+            IndexPair::new(row.index - index, row.new_index.unwrap())
+        } else if let Some(row) = rows
+            .iter()
+            .take(index)
+            .rev()
+            .find(|row| row.new_index.is_some())
+        {
+            assert!(row.index < index);
+            IndexPair::new(0, row.new_index.unwrap())
+        } else {
+            IndexPair::new(0, 0)
+        }
+    }
+
+    pub fn find_index_from_old_index(
+        &self,
+        old_index: usize,
+        options: &DiffOptions,
+    ) -> Option<usize> {
+        self.rows(options)
+            .iter()
+            .find(|row| row.contains_old_index(old_index))
+            .map(|row| row.index)
+    }
+
+    pub fn find_index_from_new_index(
+        &self,
+        new_index: usize,
+        options: &DiffOptions,
+    ) -> Option<usize> {
+        self.rows(options)
+            .iter()
+            .find(|row| row.contains_new_index(new_index))
+            .map(|row| row.index)
+    }
+}
+
+// A line for each changed key path, where values of a changed key are shown as a word diff. Lines
+// have no line numbers.
+fn calc_structured_lines(changes: &[DataChange]) -> Vec<DiffLine> {
+    let mut lines = vec![];
+    for change in changes {
+        let mut parts = vec![DiffLinePart::new(format!("{}: ", change.path()), false)];
+        let tag = match (change.old_value(), change.new_value()) {
+            (Some(old_value), Some(new_value)) => {
+                parts.push(
+                    DiffLinePart::new(format!("[-{}-]", old_value), false)
+                        .with_word_tag(ChangeTag::Delete),
+                );
+                parts.push(
+                    DiffLinePart::new(format!("{{+{}+}}", new_value), false)
+                        .with_word_tag(ChangeTag::Insert),
+                );
+                ChangeTag::Equal
+            }
+            (Some(old_value), None) => {
+                parts.push(DiffLinePart::new(old_value, false));
+                ChangeTag::Delete
+            }
+            (None, new_value) => {
+                parts.push(DiffLinePart::new(new_value.unwrap_or_default(), false));
+                ChangeTag::Insert
+            }
+        };
+        parts.push(DiffLinePart::new("\n", false));
+        lines.push(DiffLine::new(None, None, tag, parts));
+    }
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut line)| {
+            line.index = index;
+            line
+        })
+        .collect()
+}
+
+fn calc_rows(lines: &[DiffLine], options: &DiffOptions) -> Vec<DiffRow> {
+    let rows = match options.layout {
+        DiffLayout::Unified => lines
+            .iter()
+            .map(|line| {
+                DiffRow::new(
+                    DiffRowKind::Line(line.index),
+                    line.old_index,
+                    line.new_index,
+                )
+            })
+            .collect::<Vec<_>>(),
+        DiffLayout::Split => {
+            let mut rows = vec![];
+            let mut i = 0;
+            while i < lines.len() {
+                let line = &lines[i];
+                // Changed words are shown on both sides, even without line numbers of either side,
+                // e.g. changed values of key paths in a structured diff.
+                if line.tag == ChangeTag::Equal && line.has_changed_words() {
+                    rows.push(DiffRow::new(
+                        DiffRowKind::Pair(Some(i), Some(i)),
+                        line.old_index,
+                        line.new_index,
+                    ));
+                    i += 1;
+                    continue;
+                }
+                if line.tag == ChangeTag::Equal {
+                    rows.push(DiffRow::new(
+                        DiffRowKind::Pair(line.old_index.map(|_| i), line.new_index.map(|_| i)),
+                        line.old_index,
+                        line.new_index,
+                    ));
+                    i += 1;
+                    continue;
+                }
+
+                // align deleted lines and inserted lines in a changed block
+                let start = i;
+                while i < lines.len() && lines[i].tag != ChangeTag::Equal {
+                    i += 1;
+                }
+                let deleted = (start..i)
+                    .filter(|j| lines[*j].tag == ChangeTag::Delete)
+                    .collect::<Vec<_>>();
+                let inserted = (start..i)
+                    .filter(|j| lines[*j].tag == ChangeTag::Insert)
+                    .collect::<Vec<_>>();
+                for k in 0..cmp::max(deleted.len(), inserted.len()) {
+                    let old = deleted.get(k).copied();
+                    let new = inserted.get(k).copied();
+                    rows.push(DiffRow::new(
+                        DiffRowKind::Pair(old, new),
+                        old.and_then(|j| lines[j].old_index),
+                        new.and_then(|j| lines[j].new_index),
+                    ));
+                }
+            }
+            rows
+        }
+    };
+
+    let rows = match options.context_lines {
+        Some(context_lines) => fold_rows(rows, lines, context_lines, &options.expanded_folds),
+        None => rows,
+    };
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(index, mut row)| {
+            row.index = index;
+            row
+        })
+        .collect()
+}
+
+// Fold unchanged rows far from changes, except for expanded folds.
+fn fold_rows(
+    rows: Vec<DiffRow>,
+    lines: &[DiffLine],
+    context_lines: usize,
+    expanded_folds: &[usize],
+) -> Vec<DiffRow> {
+    let mut is_near_change = vec![false; lines.len()];
+    for line in lines.iter().filter(|line| line.is_changed()) {
+        let start = line.index.saturating_sub(context_lines);
+        let end = cmp::min(line.index + context_lines + 1, lines.len());
+        is_near_change[start..end].fill(true);
+    }
+
+    let mut folded_rows = vec![];
+    let mut hidden_rows: Vec<DiffRow> = vec![];
+    let mut rows = rows.into_iter().peekable();
+    while let Some(row) = rows.next() {
+        if let Some(index) = row
+            .kind
+            .line_index()
+            .filter(|index| !is_near_change[*index])
+        {
+            hidden_rows.push(row);
+            let is_last = rows
+                .peek()
+                .and_then(|next_row| next_row.kind.line_index())
+                .is_none_or(|next_index| is_near_change[next_index]);
+            if !is_last {
+                continue;
+            }
+
+            let start = hidden_rows[0].kind.line_index().unwrap();
+            // a fold of a single line takes as much space as the line
+            if index == start || expanded_folds.contains(&start) {
+                folded_rows.append(&mut hidden_rows);
+            } else {
+                folded_rows.push(DiffRow {
+                    index: 0,
+                    old_index: hidden_rows.iter().find_map(|row| row.old_index),
+                    new_index: hidden_rows.iter().find_map(|row| row.new_index),
+                    old_last_index: hidden_rows.iter().rev().find_map(|row| row.old_last_index),
+                    new_last_index: hidden_rows.iter().rev().find_map(|row| row.new_last_index),
+                    kind: DiffRowKind::Fold(start, index + 1),
+                });
+                hidden_rows.clear();
+            }
+        } else {
+            folded_rows.push(row);
+        }
+    }
+    folded_rows
+}
+
+const HEX_BYTES_PER_LINE: usize = 16;
+const HEX_DIFF_TIMEOUT: Duration = Duration::from_millis(500); // unless `--diff-timeout` is set
+const MOVED_MIN_ALNUM_COUNT: usize = 20; // cf. `COLOR_MOVED_MIN_ALNUM_COUNT` of git
+const MOVED_MIN_LINE_ALNUM_COUNT: usize = 4; // for a line to start a moved block
+const MOVED_MAX_CANDIDATES: usize = 64; // inserted lines compared with each deleted line
+
+struct HexRegion {
+    old_range: Range<usize>,
+    new_range: Range<usize>,
+    is_changed: bool,
+}
+
+impl HexRegion {
+    fn extend(&mut self, op: &DiffOp) {
+        self.old_range.end = op.old_range().end;
+        self.new_range.end = op.new_range().end;
+    }
+}
+
+// Lines of a word diff, which are broken at line endings of both versions. A line has the index
+// of an old/new line only if the line shows the start of it.
+struct WordDiffBuilder {
+    lines: Vec<DiffLine>,
+    parts: Vec<(ChangeTag, String)>,
+    tags: Vec<ChangeTag>,                // of words in the current line
+    pending_line_ending: Option<String>, // a deleted line ending, which may be replaced
+    old_index: usize,
+    new_index: usize,
+    last_old_index: Option<usize>,
+    last_new_index: Option<usize>,
+}
+
+impl WordDiffBuilder {
+    fn new(old_index: usize, new_index: usize) -> Self {
+        Self {
+            lines: vec![],
+            parts: vec![],
+            tags: vec![],
+            pending_line_ending: None,
+            old_index,
+            new_index,
+            last_old_index: None,
+            last_new_index: None,
+        }
+    }
+
+    fn push(&mut self, word: &str, tag: ChangeTag, is_line_ending: bool) {
+        // inserted words are in the same line as the deleted line ending
+        if let Some(old_ending) = self.pending_line_ending.take() {
+            if tag == ChangeTag::Insert && !is_line_ending {
+                self.pending_line_ending = Some(old_ending);
+            } else if tag == ChangeTag::Insert {
+                // e.g. a line ending changed from CRLF to LF
+                self.tags.push(ChangeTag::Insert);
+                self.push_part(ChangeTag::Equal, word);
+                let change = (
+                    LineEnding::of(old_ending.as_bytes()),
+                    LineEnding::of(word.as_bytes()),
+                );
+                self.finish_line(Some(change).filter(|(old, new)| old != new));
+                self.old_index += 1;
+                self.new_index += 1;
+                return;
+            } else {
+                self.push_part(ChangeTag::Equal, &old_ending);
+                self.finish_line(None);
+                self.old_index += 1;
+            }
+        }
+
+        self.tags.push(tag);
+        if !is_line_ending {
+            self.push_part(tag, word);
+            return;
+        }
+        if tag == ChangeTag::Delete {
+            self.pending_line_ending = Some(String::from(word));
+            return;
+        }
+        // line endings are not enclosed with markers
+        self.push_part(ChangeTag::Equal, word);
+        self.finish_line(None);
+        if tag == ChangeTag::Equal {
+            self.old_index += 1;
+        }
+        self.new_index += 1;
+    }
+
+    fn push_part(&mut self, tag: ChangeTag, word: &str) {
+        match self.parts.last_mut() {
+            Some((last_tag, text)) if *last_tag == tag => text.push_str(word),
+            _ => self.parts.push((tag, String::from(word))),
+        }
+    }
+
+    fn finish_line(&mut self, line_ending_change: Option<(LineEnding, LineEnding)>) {
+        let has = |tag| self.tags.contains(&tag);
+        let tag = match (
+            has(ChangeTag::Delete),
+            has(ChangeTag::Insert),
+            has(ChangeTag::Equal),
+        ) {
+            (true, false, false) => ChangeTag::Delete,
+            (false, true, false) => ChangeTag::Insert,
+            _ => ChangeTag::Equal,
+        };
+        let old_index = Some(self.old_index)
+            .filter(|_| has(ChangeTag::Delete) || has(ChangeTag::Equal))
+            .filter(|index| self.last_old_index != Some(*index));
+        let new_index = Some(self.new_index)
+            .filter(|_| has(ChangeTag::Insert) || has(ChangeTag::Equal))
+            .filter(|index| self.last_new_index != Some(*index));
+        self.last_old_index = old_index.or(self.last_old_index);
+        self.last_new_index = new_index.or(self.last_new_index);
+
+        let parts = self
+            .parts
+            .drain(..)
+            .map(|(tag, text)| match tag {
+                ChangeTag::Delete => DiffLinePart::new(format!("[-{}-]", text), false)
+                    .with_word_tag(ChangeTag::Delete),
+                ChangeTag::Insert => DiffLinePart::new(format!("{{+{}+}}", text), false)
+                    .with_word_tag(ChangeTag::Insert),
+                ChangeTag::Equal => DiffLinePart::new(text, false),
+            })
+            .collect();
+        let mut line = DiffLine::new(old_index, new_index, tag, parts);
+        line.line_ending_change = line_ending_change;
+        self.lines.push(line);
+        self.tags.clear();
+    }
+
+    fn finish(mut self) -> Vec<DiffLine> {
+        if let Some(old_ending) = self.pending_line_ending.take() {
+            let has_changed_words = self.parts.iter().any(|(tag, _)| *tag != ChangeTag::Equal);
+            if self.parts.is_empty() || has_changed_words {
+                self.push_part(ChangeTag::Equal, &old_ending);
+                self.finish_line(None);
+            } else {
+                // the same last line except that the new file has no newline at the end
+                let change = (LineEnding::of(old_ending.as_bytes()), LineEnding::None);
+                self.finish_line(Some(change));
+            }
+        } else if !self.parts.is_empty() {
+            self.finish_line(None);
         }
+        self.lines
     }
+}
 
-    fn calc_lines(&self) -> Vec<DiffLine> {
-        let old_file_text = if self.has_old_binary_file {
-            vec![]
+// split a range of bytes at multiples of `HEX_BYTES_PER_LINE`
+fn hex_line_ranges(range: Range<usize>) -> impl Iterator<Item = Range<usize>> {
+    let mut start = range.start;
+    iter::from_fn(move || {
+        if start >= range.end {
+            return None;
+        }
+        let end = cmp::min(
+            (start / HEX_BYTES_PER_LINE + 1) * HEX_BYTES_PER_LINE,
+            range.end,
+        );
+        let line_range = start..end;
+        start = end;
+        Some(line_range)
+    })
+}
+
+// e.g. `00000010  48 65 6c 6c 6f                                    |Hello|`
+fn hex_line_parts(offset: usize, bytes: &[u8], is_changed: &[bool]) -> Vec<DiffLinePart> {
+    // bytes are placed at their columns of `offset % HEX_BYTES_PER_LINE`
+    let padding = offset % HEX_BYTES_PER_LINE;
+    let mut parts = vec![DiffLinePart::new(
+        format!("{:08x} ", offset - padding) + &"   ".repeat(padding),
+        false,
+    )];
+    for (byte, is_changed) in bytes.iter().zip(is_changed) {
+        parts.push(DiffLinePart::new(" ", false));
+        parts.push(DiffLinePart::new(format!("{:02x}", byte), *is_changed));
+    }
+    parts.push(DiffLinePart::new(
+        " ".repeat((HEX_BYTES_PER_LINE - padding - bytes.len()) * 3 + 2)
+            + "|"
+            + &" ".repeat(padding),
+        false,
+    ));
+    for (byte, is_changed) in bytes.iter().zip(is_changed) {
+        let c = if byte.is_ascii_graphic() || *byte == b' ' {
+            char::from(*byte)
         } else {
-            self.repo
-                .find_blob(self.old_file_oid)
-                .map(|blob| blob.content().to_vec())
-                .unwrap_or_default()
+            '.'
         };
+        parts.push(DiffLinePart::new(c.to_string(), *is_changed));
+    }
+    parts.push(DiffLinePart::new(
+        " ".repeat(HEX_BYTES_PER_LINE - padding - bytes.len()) + "|",
+        false,
+    ));
+    parts
+}
 
-        assert!(!self.has_new_binary_file);
-        let new_file_text = self
-            .repo
-            .find_blob(self.new_file_oid)
-            .map(|blob| blob.content().to_vec())
-            .unwrap_or_default();
+// Pair blocks of deleted lines with the same blocks of inserted lines, cf. `git diff --color-moved`
+fn detect_moves(lines: &mut [DiffLine]) {
+    let texts = lines
+        .iter()
+        .map(|line| {
+            line.parts
+                .iter()
+                .map(|part| part.text.as_str())
+                .collect::<String>()
+                .trim_end_matches(&['\r', '\n'][..])
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    // blank or short lines like closing braces do not start blocks, or they are paired everywhere
+    let can_start_block: Vec<bool> = texts
+        .iter()
+        .map(|text| {
+            text.chars().filter(|c| c.is_alphanumeric()).count() >= MOVED_MIN_LINE_ALNUM_COUNT
+        })
+        .collect();
+    let mut inserted_indices: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.tag == ChangeTag::Insert && can_start_block[i] {
+            inserted_indices
+                .entry(texts[i].as_str())
+                .or_default()
+                .push(i);
+        }
+    }
 
-        let text_diff = TextDiff::from_lines(&old_file_text, &new_file_text);
-        text_diff
-            .ops()
-            .iter()
-            .flat_map(|op| {
-                text_diff.iter_inline_changes(op).map(|change| {
-                    let parts = change
-                        .iter_strings_lossy()
-                        .map(|(emphasized, text)| {
-                            DiffLinePart::new(text.replace('\t', &self.args.tab_spaces), emphasized)
-                        })
-                        .collect();
-                    DiffLine::new(change.old_index(), change.new_index(), change.tag(), parts)
+    let mut is_moved = vec![false; lines.len()];
+    let mut moves = vec![]; // (deleted start, inserted start, length)
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].tag != ChangeTag::Delete || !can_start_block[i] {
+            i += 1;
+            continue;
+        }
+        let block_len = |j: usize| {
+            (0..)
+                .take_while(|k| {
+                    i + k < lines.len()
+                        && j + k < lines.len()
+                        && lines[i + k].tag == ChangeTag::Delete
+                        && lines[j + k].tag == ChangeTag::Insert
+                        && !is_moved[j + k]
+                        && texts[i + k] == texts[j + k]
                 })
-            })
-            .enumerate()
-            .map(|(index, mut line)| {
-                line.index = index;
-                line
-            })
-            .collect::<Vec<_>>()
+                .count()
+        };
+        // the longest block, or the first one of them
+        let block = inserted_indices
+            .get(texts[i].as_str())
+            .into_iter()
+            .flatten()
+            .filter(|j| !is_moved[**j])
+            .take(MOVED_MAX_CANDIDATES)
+            .map(|j| (*j, block_len(*j)))
+            .fold(
+                None,
+                |longest: Option<(usize, usize)>, (j, len)| match longest {
+                    Some((_, longest_len)) if longest_len >= len => longest,
+                    _ => Some((j, len)),
+                },
+            );
+        match block {
+            // ignore short blocks like closing braces
+            Some((j, len))
+                if texts[i..i + len]
+                    .iter()
+                    .flat_map(|text| text.chars())
+                    .filter(|c| c.is_alphanumeric())
+                    .count()
+                    >= MOVED_MIN_ALNUM_COUNT =>
+            {
+                is_moved[j..j + len].fill(true);
+                moves.push((i, j, len));
+                i += len;
+            }
+            _ => i += 1,
+        }
     }
 
-    pub fn status(&self) -> String {
-        match self.status {
-            Delta::Modified => format!("* Modified: {}", self.new_path.as_deref().unwrap()),
-            Delta::Added => format!("* Added: {}", self.new_path.as_deref().unwrap()),
-            Delta::Deleted => format!("* Deleted: {}", self.new_path.as_deref().unwrap()),
-            Delta::Renamed => format!(
-                "* Renamed: {} -> {}",
-                self.old_path.as_deref().unwrap(),
-                self.new_path.as_deref().unwrap()
-            ),
-            _ => unreachable!(),
+    for (id, (deleted_start, inserted_start, len)) in moves.into_iter().enumerate() {
+        for k in 0..len {
+            lines[deleted_start + k].moved = Some(Move::new(id, inserted_start, k == 0));
+            lines[inserted_start + k].moved = Some(Move::new(id, deleted_start, k == 0));
         }
     }
+}
 
-    pub fn max_line_number_len(&self) -> usize {
-        self.lines()
-            .unwrap_or(&vec![])
-            .iter()
-            .filter_map(|change| {
-                cmp::max(change.old_index, change.new_index).map(|x| {
-                    // 0-indexed to 1-indexed
-                    x + 1
-                })
-            })
-            .fold(0, cmp::max)
-            .to_string()
-            .len()
+// split parts at boundaries of tokens to color them
+fn highlight_parts(parts: Vec<DiffLinePart>, tokens: &[Token]) -> Vec<DiffLinePart> {
+    let mut highlighted_parts = vec![];
+    let mut tokens = tokens.iter().filter(|token| token.text_len() > 0);
+    let mut token = tokens.next();
+    let mut token_rest = token.map_or(0, |token| token.text_len());
+    for part in parts.iter() {
+        let mut text = part.text();
+        while !text.is_empty() {
+            let current_token = match token {
+                Some(token) => token,
+                None => return parts,
+            };
+            let len = cmp::min(token_rest, text.len());
+            if !text.is_char_boundary(len) {
+                return parts;
+            }
+            let (head, tail) = text.split_at(len);
+            highlighted_parts
+                .push(DiffLinePart::new(head, part.emphasized).with_color(current_token.color()));
+            text = tail;
+            token_rest -= len;
+            if token_rest == 0 {
+                token = tokens.next();
+                token_rest = token.map_or(0, |token| token.text_len());
+            }
+        }
     }
+    highlighted_parts
+}
 
-    pub fn allowed_min_index(&self, _state: &State) -> usize {
-        0
+// split lines in the same way as `similar`
+fn split_lines(text: &[u8]) -> Vec<&[u8]> {
+    let mut lines = vec![];
+    let mut last_pos = 0;
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            b'\r' if text.get(i + 1) == Some(&b'\n') => {
+                lines.push(&text[last_pos..i + 2]);
+                i += 1;
+                last_pos = i + 1;
+            }
+            b'\r' | b'\n' => {
+                lines.push(&text[last_pos..=i]);
+                last_pos = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if last_pos < text.len() {
+        lines.push(&text[last_pos..]);
     }
+    lines
+}
 
-    pub fn allowed_max_index(&self, state: &State) -> usize {
-        if let Some(lines) = self.lines() {
-            let diff_length = lines.len();
-            let diff_height = Dashboard::diff_height(state.terminal_height());
+// byte offsets of the start of each line, and the end of the text
+fn line_offsets(lines: &[&[u8]]) -> Vec<usize> {
+    iter::once(0)
+        .chain(lines.iter().scan(0, |offset, line| {
+            *offset += line.len();
+            Some(*offset)
+        }))
+        .collect()
+}
 
-            if state.args().beyond_last_line {
-                diff_length.saturating_sub(1)
-            } else {
-                diff_length.saturating_sub(cmp::max(1, diff_height))
+fn normalize_line(line: &[u8], mode: WhitespaceMode) -> Cow<'_, str> {
+    let line = String::from_utf8_lossy(line);
+    match mode {
+        // Line endings are compared separately without ignoring CR at EOL. Otherwise, only one CR
+        // just before LF is ignored, where a lone CR is also a line terminator.
+        WhitespaceMode::None | WhitespaceMode::IgnoreCrAtEol => match line
+            .strip_suffix("\r\n")
+            .or_else(|| line.strip_suffix('\r'))
+        {
+            Some(content) => Cow::Owned(format!("{}\n", content)),
+            None => line,
+        },
+        // Each run of whitespace is collapsed into one space, including a leading one, and
+        // whitespace at the end of the line is ignored as `git diff -b` does.
+        WhitespaceMode::IgnoreSpaceChange => {
+            let mut normalized = String::with_capacity(line.len());
+            let mut is_in_whitespace = false;
+            for c in line.trim_end().chars() {
+                if c.is_whitespace() {
+                    is_in_whitespace = true;
+                } else {
+                    if is_in_whitespace {
+                        normalized.push(' ');
+                        is_in_whitespace = false;
+                    }
+                    normalized.push(c);
+                }
             }
-        } else {
-            0
+            Cow::Owned(normalized)
+        }
+        WhitespaceMode::IgnoreAllSpace => {
+            Cow::Owned(line.chars().filter(|c| !c.is_whitespace()).collect())
         }
     }
+}
 
-    pub fn can_move_up(&self, index: usize, state: &State) -> bool {
-        index > self.allowed_min_index(state)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffLayout {
+    Unified,
+    Split, // side-by-side
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DiffOptions {
+    algorithm: DiffAlgorithm,
+    whitespace_mode: WhitespaceMode,
+    ignore_blank_lines: bool,
+    granularity: Granularity,
+    word_diff: bool,
+    structured: bool, // compare data files by their structures
+    color_moved: bool,
+    layout: DiffLayout,
+    context_lines: Option<usize>, // `None` to show all lines
+    expanded_folds: Vec<usize>,   // sorted start indices of expanded folds
+}
+
+impl DiffOptions {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            algorithm: args.diff_algorithm,
+            whitespace_mode: args.whitespace_mode,
+            ignore_blank_lines: args.ignore_blank_lines,
+            granularity: args.emphasis_granularity,
+            word_diff: args.word_diff,
+            structured: args.structured_diff,
+            color_moved: args.color_moved,
+            layout: DiffLayout::Unified,
+            context_lines: if args.hunk_only {
+                Some(args.context_lines)
+            } else {
+                None
+            },
+            expanded_folds: vec![],
+        }
     }
 
-    pub fn can_move_down(&self, index: usize, state: &State) -> bool {
-        index < self.allowed_max_index(state)
+    pub fn algorithm(&self) -> DiffAlgorithm {
+        self.algorithm
     }
 
-    pub fn nearest_old_index_pair(&self, index: usize) -> IndexPair {
-        if let Some(lines) = self.lines() {
-            if let Some(line) = lines
-                .iter()
-                .skip(index)
-                .find(|line| line.old_index.is_some())
-            {
-                assert!(line.index >= index);
-                IndexPair::new(line.index - index, line.old_index.unwrap())
-            } else if let Some(line) = lines
-                .iter()
-                .take(index)
-                .rev()
-                .find(|line| line.old_index.is_some())
-            {
-                assert!(line.index < index);
-                IndexPair::new(0, line.old_index.unwrap())
-            } else {
-                IndexPair::new(0, 0)
-            }
-        } else {
-            IndexPair::new(0, 0)
+    pub fn whitespace_mode(&self) -> WhitespaceMode {
+        self.whitespace_mode
+    }
+
+    pub fn ignore_blank_lines(&self) -> bool {
+        self.ignore_blank_lines
+    }
+
+    pub fn granularity(&self) -> Granularity {
+        self.granularity
+    }
+
+    pub fn word_diff(&self) -> bool {
+        self.word_diff
+    }
+
+    pub fn structured(&self) -> bool {
+        self.structured
+    }
+
+    pub fn color_moved(&self) -> bool {
+        self.color_moved
+    }
+
+    pub fn layout(&self) -> DiffLayout {
+        self.layout
+    }
+
+    pub fn with_layout(self, layout: DiffLayout) -> Self {
+        Self { layout, ..self }
+    }
+
+    pub fn context_lines(&self) -> Option<usize> {
+        self.context_lines
+    }
+
+    pub fn with_context_lines(self, context_lines: Option<usize>) -> Self {
+        Self {
+            context_lines,
+            ..self
         }
     }
 
-    pub fn nearest_new_index_pair(&self, index: usize) -> IndexPair {
-        if let Some(lines) = self.lines() {
-            if let Some(line) = lines
-                .iter()
-                .skip(index)
-                .find(|line| line.new_index.is_some())
-            {
-                assert!(line.index >= index);
-                IndexPair::new(line.index - index, line.new_index.unwrap())
-            } else if let Some(line) = lines
-                .iter()
-                .take(index)
-                .rev()
-                .find(|line| line.new_index.is_some())
-            {
-                assert!(line.index < index);
-                IndexPair::new(0, line.new_index.unwrap())
-            } else {
-                IndexPair::new(0, 0)
-            }
-        } else {
-            IndexPair::new(0, 0)
+    pub fn expanded_folds(&self) -> &[usize] {
+        &self.expanded_folds
+    }
+
+    pub fn with_expanded_folds(self, expanded_folds: Vec<usize>) -> Self {
+        Self {
+            expanded_folds,
+            ..self
         }
     }
 
-    pub fn find_index_from_old_index(&self, old_index: usize) -> Option<usize> {
-        self.lines().and_then(|lines| {
-            lines
-                .iter()
-                .find(|line| {
-                    line.old_index
-                        // use https://doc.rust-lang.org/std/option/enum.Option.html#method.contains in the future
-                        .filter(|i| *i == old_index)
-                        .is_some()
-                })
-                .map(|line| line.index)
-        })
+    pub fn with_algorithm(self, algorithm: DiffAlgorithm) -> Self {
+        Self { algorithm, ..self }
     }
 
-    pub fn find_index_from_new_index(&self, new_index: usize) -> Option<usize> {
-        self.lines().and_then(|lines| {
-            lines
-                .iter()
-                .find(|line| {
-                    line.new_index
-                        // use https://doc.rust-lang.org/std/option/enum.Option.html#method.contains in the future
-                        .filter(|i| *i == new_index)
-                        .is_some()
-                })
-                .map(|line| line.index)
-        })
+    pub fn with_whitespace_mode(self, whitespace_mode: WhitespaceMode) -> Self {
+        Self {
+            whitespace_mode,
+            ..self
+        }
+    }
+
+    pub fn with_ignore_blank_lines(self, ignore_blank_lines: bool) -> Self {
+        Self {
+            ignore_blank_lines,
+            ..self
+        }
+    }
+
+    pub fn with_granularity(self, granularity: Granularity) -> Self {
+        Self {
+            granularity,
+            ..self
+        }
+    }
+
+    pub fn with_word_diff(self, word_diff: bool) -> Self {
+        Self { word_diff, ..self }
+    }
+
+    pub fn with_structured(self, structured: bool) -> Self {
+        Self { structured, ..self }
+    }
+
+    pub fn with_color_moved(self, color_moved: bool) -> Self {
+        Self {
+            color_moved,
+            ..self
+        }
+    }
+}
+
+impl From<DiffAlgorithm> for Algorithm {
+    fn from(algorithm: DiffAlgorithm) -> Self {
+        match algorithm {
+            DiffAlgorithm::Myers => Algorithm::Myers,
+            DiffAlgorithm::Patience => Algorithm::Patience,
+            DiffAlgorithm::Lcs => Algorithm::Lcs,
+        }
     }
 }
 
@@ -240,6 +1964,8 @@ pub struct DiffLine {
     new_index: Option<usize>,
     tag: ChangeTag,
     parts: Vec<DiffLinePart>,
+    line_ending_change: Option<(LineEnding, LineEnding)>, // for lines changed only in line endings
+    moved: Option<Move>,
 }
 
 impl DiffLine {
@@ -255,6 +1981,8 @@ impl DiffLine {
             new_index,
             tag,
             parts,
+            line_ending_change: None,
+            moved: None,
         }
     }
 
@@ -266,7 +1994,43 @@ impl DiffLine {
         self.new_index.map(|index| index + 1)
     }
 
+    pub fn is_changed(&self) -> bool {
+        self.tag != ChangeTag::Equal
+            || self.line_ending_change.is_some()
+            || self.has_changed_words()
+    }
+
+    // for lines of a word diff
+    fn has_changed_words(&self) -> bool {
+        self.parts.iter().any(|part| part.word_tag.is_some())
+    }
+
+    // the first line of the other end of a moved block
+    pub fn move_pair_start(&self) -> Option<usize> {
+        self.moved.map(|moved| moved.pair_start)
+    }
+
+    pub fn is_move_start(&self) -> bool {
+        self.moved.is_some_and(|moved| moved.is_start)
+    }
+
+    pub fn line_ending_change(&self) -> Option<(LineEnding, LineEnding)> {
+        self.line_ending_change
+    }
+
+    // cf. "\ No newline at end of file" of `git diff`
+    pub fn has_final_newline(&self) -> bool {
+        self.parts
+            .last()
+            .is_none_or(|part| part.text.ends_with(&['\n', '\r'][..]))
+    }
+
     pub fn sign(&self) -> String {
+        if self.line_ending_change.is_some()
+            || (self.tag == ChangeTag::Equal && self.has_changed_words())
+        {
+            return String::from("~");
+        }
         match self.tag {
             ChangeTag::Delete => String::from("-"),
             ChangeTag::Insert => String::from("+"),
@@ -275,6 +2039,19 @@ impl DiffLine {
     }
 
     pub fn style(&self) -> Style {
+        if self.line_ending_change.is_some() {
+            return Style::default().fg(Color::Yellow);
+        }
+        // alternate colors for adjacent moved blocks, cf. `zebra` of `git diff --color-moved`
+        if let Some(moved) = self.moved {
+            let is_odd = moved.id % 2 == 1;
+            return match (self.tag, is_odd) {
+                (ChangeTag::Delete, false) => Style::default().fg(Color::Magenta),
+                (ChangeTag::Delete, true) => Style::default().fg(Color::LightMagenta),
+                (_, false) => Style::default().fg(Color::Cyan),
+                (_, true) => Style::default().fg(Color::LightCyan),
+            };
+        }
         match self.tag {
             ChangeTag::Delete => Style::default().fg(Color::Red),
             ChangeTag::Insert => Style::default().fg(Color::Green),
@@ -285,12 +2062,190 @@ impl DiffLine {
     pub fn parts(&self) -> &Vec<DiffLinePart> {
         &self.parts
     }
+
+    // Texts of parts shown in a terminal. Tabs are expanded to tab stops from the start of the text,
+    // and line endings are removed unless whitespace is shown. Changed words with `hidden_word_tag`
+    // are empty and take no columns, e.g. inserted words on the old side of a split word diff.
+    pub fn display_texts(
+        &self,
+        tab_size: usize,
+        should_show_whitespace: bool,
+        hidden_word_tag: Option<ChangeTag>,
+    ) -> Vec<String> {
+        let text_len = self.parts.iter().map(|part| part.text.len()).sum::<usize>();
+        let trailing_whitespace_offset = self
+            .parts
+            .iter()
+            .map(|part| part.text.as_str())
+            .collect::<String>()
+            .trim_end_matches(|c: char| c.is_whitespace())
+            .len();
+
+        let mut texts = vec![];
+        let mut offset = 0;
+        let mut column = 0;
+        for part in self.parts.iter() {
+            let mut text = String::new();
+            if hidden_word_tag.is_some() && part.word_tag == hidden_word_tag {
+                offset += part.text.len();
+                texts.push(text);
+                continue;
+            }
+            for c in part.text.chars() {
+                let is_trailing = offset >= trailing_whitespace_offset;
+                offset += c.len_utf8();
+                match c {
+                    '\n' if offset == text_len => {}
+                    '\r' => {
+                        if should_show_whitespace {
+                            text.push('␍');
+                            column += 1;
+                        }
+                    }
+                    '\t' => {
+                        let width = tab_size - column % tab_size;
+                        if should_show_whitespace {
+                            text.push('→');
+                            text.push_str(&" ".repeat(width - 1));
+                        } else {
+                            text.push_str(&" ".repeat(width));
+                        }
+                        column += width;
+                    }
+                    ' ' if should_show_whitespace && is_trailing => {
+                        text.push('·');
+                        column += 1;
+                    }
+                    '\u{a0}' if should_show_whitespace => {
+                        text.push('⍽');
+                        column += 1;
+                    }
+                    c => {
+                        text.push(c);
+                        column += UnicodeWidthChar::width(c).unwrap_or(0);
+                    }
+                }
+            }
+            texts.push(text);
+        }
+        texts
+    }
+
+    // layer the diff coloring and inline emphasis on syntax highlighting
+    pub fn part_style(&self, part: &DiffLinePart, should_emphasize: bool) -> Style {
+        // moved lines are the same as their other ends
+        let is_emphasized = should_emphasize && part.emphasized && self.moved.is_none();
+        match part.word_tag {
+            Some(ChangeTag::Delete) => return Style::default().fg(Color::Red),
+            Some(ChangeTag::Insert) => return Style::default().fg(Color::Green),
+            _ => {}
+        }
+        match part.color {
+            Some(color) => {
+                let style = Style::default().fg(color);
+                match (self.tag, is_emphasized) {
+                    (ChangeTag::Delete, _) if self.moved.is_some() => {
+                        style.bg(Color::Rgb(64, 24, 72))
+                    }
+                    (ChangeTag::Insert, _) if self.moved.is_some() => {
+                        style.bg(Color::Rgb(16, 56, 72))
+                    }
+                    (ChangeTag::Delete, false) => style.bg(Color::Rgb(72, 24, 24)),
+                    (ChangeTag::Delete, true) => style.bg(Color::Rgb(136, 40, 40)),
+                    (ChangeTag::Insert, false) => style.bg(Color::Rgb(24, 64, 24)),
+                    (ChangeTag::Insert, true) => style.bg(Color::Rgb(40, 120, 40)),
+                    (ChangeTag::Equal, _) => style,
+                }
+            }
+            None if is_emphasized => part.emphasize(self.style()),
+            None => self.style(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Move {
+    id: usize,
+    pair_start: usize, // an index of the first line of the other end
+    is_start: bool,
+}
+
+impl Move {
+    fn new(id: usize, pair_start: usize, is_start: bool) -> Self {
+        Self {
+            id,
+            pair_start,
+            is_start,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DiffRow {
+    index: usize,
+    old_index: Option<usize>,
+    new_index: Option<usize>,
+    old_last_index: Option<usize>, // differs from `old_index` only for a fold
+    new_last_index: Option<usize>, // differs from `new_index` only for a fold
+    kind: DiffRowKind,
+}
+
+// indices of `Diff::lines()`
+#[derive(Debug, Clone, Copy)]
+pub enum DiffRowKind {
+    Line(usize),
+    Pair(Option<usize>, Option<usize>), // (an old line, a new line) in the split layout
+    Fold(usize, usize),                 // a range of hidden lines
+}
+
+impl DiffRowKind {
+    // a line to decide whether the row is hidden
+    fn line_index(&self) -> Option<usize> {
+        match *self {
+            DiffRowKind::Line(index) => Some(index),
+            DiffRowKind::Pair(old_index, new_index) => old_index.or(new_index),
+            DiffRowKind::Fold(_, _) => None,
+        }
+    }
+}
+
+impl DiffRow {
+    fn new(kind: DiffRowKind, old_index: Option<usize>, new_index: Option<usize>) -> Self {
+        Self {
+            index: 0,
+            old_index,
+            new_index,
+            old_last_index: old_index,
+            new_last_index: new_index,
+            kind,
+        }
+    }
+
+    pub fn kind(&self) -> DiffRowKind {
+        self.kind
+    }
+
+    fn contains_old_index(&self, index: usize) -> bool {
+        match (self.old_index, self.old_last_index) {
+            (Some(first), Some(last)) => first <= index && index <= last,
+            _ => false,
+        }
+    }
+
+    fn contains_new_index(&self, index: usize) -> bool {
+        match (self.new_index, self.new_last_index) {
+            (Some(first), Some(last)) => first <= index && index <= last,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct DiffLinePart {
     text: String,
     emphasized: bool,
+    color: Option<Color>,        // for syntax highlighting
+    word_tag: Option<ChangeTag>, // for changed words of a word diff
 }
 
 impl DiffLinePart {
@@ -298,6 +2253,26 @@ impl DiffLinePart {
         Self {
             text: text.into(),
             emphasized,
+            color: None,
+            word_tag: None,
+        }
+    }
+
+    fn with_word_tag(self, word_tag: ChangeTag) -> Self {
+        Self {
+            word_tag: Some(word_tag),
+            ..self
+        }
+    }
+
+    pub fn word_tag(&self) -> Option<ChangeTag> {
+        self.word_tag
+    }
+
+    fn with_color(self, color: Color) -> Self {
+        Self {
+            color: Some(color),
+            ..self
         }
     }
 
@@ -314,6 +2289,112 @@ impl DiffLinePart {
     }
 }
 
+#[derive(Debug)]
+pub struct LineStats {
+    additions: usize,
+    deletions: usize,
+}
+
+impl LineStats {
+    pub fn new(additions: usize, deletions: usize) -> Self {
+        Self {
+            additions,
+            deletions,
+        }
+    }
+
+    pub fn additions(&self) -> usize {
+        self.additions
+    }
+
+    pub fn deletions(&self) -> usize {
+        self.deletions
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+    None, // the last line without a newline
+}
+
+impl LineEnding {
+    fn of(line: &[u8]) -> Self {
+        if line.ends_with(b"\r\n") {
+            Self::CrLf
+        } else if line.ends_with(b"\n") {
+            Self::Lf
+        } else if line.ends_with(b"\r") {
+            Self::Cr
+        } else {
+            Self::None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+            Self::Cr => "CR",
+            Self::None => "none",
+        }
+    }
+}
+
+// numbers of lines for each line ending
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LineEndingStats {
+    lf: usize,
+    crlf: usize,
+    cr: usize,
+}
+
+impl LineEndingStats {
+    fn new(text: &[u8]) -> Self {
+        let mut stats = Self::default();
+        for line in split_lines(text) {
+            match LineEnding::of(line) {
+                LineEnding::Lf => stats.lf += 1,
+                LineEnding::CrLf => stats.crlf += 1,
+                LineEnding::Cr => stats.cr += 1,
+                LineEnding::None => {}
+            }
+        }
+        stats
+    }
+
+    pub fn has_cr(&self) -> bool {
+        self.crlf > 0 || self.cr > 0
+    }
+}
+
+impl fmt::Display for LineEndingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = [
+            (LineEnding::Lf, self.lf),
+            (LineEnding::CrLf, self.crlf),
+            (LineEnding::Cr, self.cr),
+        ]
+        .iter()
+        .copied()
+        .filter(|(_, count)| *count > 0)
+        .collect::<Vec<_>>();
+        match counts[..] {
+            [] => write!(f, "{}", LineEnding::None.name()),
+            [(ending, _)] => write!(f, "{}", ending.name()),
+            _ => {
+                let counts = counts
+                    .iter()
+                    .map(|(ending, count)| format!("{} {}", count, ending.name()))
+                    .collect::<Vec<_>>();
+                write!(f, "mixed: {}", counts.join(", "))
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct IndexPair {
     relative_index: usize, // an index from the top of a diff shown in a terminal
@@ -0,0 +1,75 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use std::borrow::Cow;
+use std::fmt;
+use std::str;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEncoding {
+    encoding: &'static Encoding,
+    has_bom: bool,
+}
+
+impl TextEncoding {
+    // `None` for binary content
+    pub fn detect(
+        content: &[u8],
+        is_binary: bool,
+        working_tree_encoding: Option<&str>,
+    ) -> Option<Self> {
+        if let Some((encoding, _)) = Encoding::for_bom(content) {
+            return Some(Self::new(encoding, true));
+        }
+        // git stores files with `working-tree-encoding` in UTF-8, except ones added before the attribute is set
+        if !is_binary && str::from_utf8(content).is_ok() {
+            return Some(Self::default());
+        }
+        let working_tree_encoding =
+            working_tree_encoding.and_then(|label| Encoding::for_label(label.as_bytes()));
+        if let Some(encoding) = working_tree_encoding {
+            return Some(Self::new(encoding, false));
+        }
+        if is_binary {
+            return None;
+        }
+
+        let mut detector = EncodingDetector::new();
+        detector.feed(content, true);
+        Some(Self::new(detector.guess(None, false), false))
+    }
+
+    fn new(encoding: &'static Encoding, has_bom: bool) -> Self {
+        Self { encoding, has_bom }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // decode content into UTF-8 without BOM
+    pub fn decode<'b>(&self, content: &'b [u8]) -> Cow<'b, [u8]> {
+        if self.is_default() {
+            return Cow::Borrowed(content);
+        }
+        match self.encoding.decode_with_bom_removal(content).0 {
+            Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+            Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+        }
+    }
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self::new(UTF_8, false)
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.encoding.name())?;
+        if self.has_bom {
+            write!(f, " with BOM")?;
+        }
+        Ok(())
+    }
+}
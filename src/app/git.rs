@@ -1,10 +1,14 @@
 use crate::app::commit::Commit;
-use crate::app::diff::Diff;
+use crate::app::diff::{Diff, LineStats};
+use crate::app::highlight::Highlighter;
 use crate::app::history::{History, TurningPoint};
+use crate::app::reference::ReferenceIndex;
 use crate::args::Args;
 use anyhow::{anyhow, Context, Result};
-use git2::{DiffFindOptions, ObjectType, Repository};
+use git2::{Config, DiffFindOptions, ObjectType, Oid, Patch, Repository};
+use std::collections::HashSet;
 use std::env;
+use std::fs;
 use std::path;
 
 pub fn get_repository() -> Result<Repository> {
@@ -16,86 +20,291 @@ pub fn get_repository() -> Result<Repository> {
     Ok(repo)
 }
 
-pub fn get_history<'a, P: AsRef<path::Path>>(
+pub fn get_path_from_repository<P: AsRef<path::Path>>(
     file_path: P,
+    repo: &Repository,
+) -> Result<path::PathBuf> {
+    env::current_dir()?
+        .join(&file_path)
+        .strip_prefix(repo.path().parent().unwrap())
+        .map(|path| path.to_path_buf())
+        .with_context(|| {
+            format!(
+                "Failed to find the file '{}' in the repository",
+                file_path.as_ref().to_string_lossy()
+            )
+        })
+}
+
+// Get the history of a file from HEAD. If the history does not reach the commit `commit_oid`,
+// e.g. the file was renamed or deleted later, get the history from the commit instead.
+pub fn get_history_at<'a>(
+    file_path_from_repository: &path::Path,
+    commit_oid: Option<Oid>,
     repo: &'a Repository,
+    reference_index: &'a ReferenceIndex,
+    highlighter: &'a Highlighter,
     args: &'a Args,
 ) -> Result<History<'a>> {
-    let file_path_from_repository = env::current_dir()
-        .unwrap()
-        .join(&file_path)
-        .strip_prefix(repo.path().parent().unwrap())
-        .unwrap()
-        .to_path_buf();
+    let history = get_history(
+        file_path_from_repository,
+        None,
+        repo,
+        reference_index,
+        highlighter,
+        args,
+    );
+    match commit_oid {
+        Some(oid) if history.as_ref().map_or(true, |h| h.find(oid).is_none()) => get_history(
+            file_path_from_repository,
+            Some(oid),
+            repo,
+            reference_index,
+            highlighter,
+            args,
+        ),
+        _ => history,
+    }
+}
+
+pub fn get_history<'a>(
+    file_path_from_repository: &path::Path,
+    start_oid: Option<Oid>, // `None` for HEAD
+    repo: &'a Repository,
+    reference_index: &'a ReferenceIndex,
+    highlighter: &'a Highlighter,
+    args: &'a Args,
+) -> Result<History<'a>> {
+    let start_name = start_oid
+        .map(|oid| format!("{}", oid))
+        .unwrap_or_else(|| String::from("HEAD"));
 
     let mut revwalk = repo
         .revwalk()
         .context("Failed to traverse the commit graph")?;
-    revwalk.push_head().context("Failed to find HEAD")?;
+    if let Some(oid) = start_oid {
+        revwalk
+            .push(oid)
+            .with_context(|| format!("Failed to find {}", start_name))?;
+    } else {
+        revwalk.push_head().context("Failed to find HEAD")?;
+    }
     revwalk.simplify_first_parent()?;
 
-    let commits = revwalk
-        .map(|oid| oid.and_then(|oid| repo.find_commit(oid)).unwrap())
+    let mut commits = revwalk
+        .map_while(|oid| oid.and_then(|oid| repo.find_commit(oid)).ok())
         .collect::<Vec<_>>();
-    let latest_file_oid = commits
+    // the revwalk fails before the boundary of a shallow clone, so follow first parents from there
+    while let Some(parent) = commits.last().and_then(|commit| commit.parent(0).ok()) {
+        commits.push(parent);
+    }
+    let latest_entry = commits
         .first()
         .context("Failed to get any commit")?
         .tree()
         .unwrap()
-        .get_path(&file_path_from_repository)
-        .with_context(|| {
-            format!(
-                "Failed to find the file '{}' on HEAD",
-                file_path.as_ref().to_string_lossy()
-            )
+        .get_path(file_path_from_repository);
+    let latest_file_oid = match latest_entry {
+        // the file is deleted by the commit
+        Err(_) if start_oid.is_some() => Oid::zero(),
+        _ => latest_entry
+            .with_context(|| {
+                format!(
+                    "Failed to find the file '{}' on {}",
+                    file_path_from_repository.to_string_lossy(),
+                    start_name
+                )
+            })
+            .and_then(|entry| {
+                if let Some(ObjectType::Blob) = entry.kind() {
+                    Ok(entry)
+                } else {
+                    Err(anyhow!(
+                        "Failed to find the path '{}' as a blob on {}",
+                        file_path_from_repository.to_string_lossy(),
+                        start_name
+                    ))
+                }
+            })?
+            .id(),
+    };
+
+    let ignored_revs = if args.skip_formatting_commits {
+        get_ignored_revs(repo)
+    } else {
+        HashSet::new()
+    };
+
+    let mut file_oid = latest_file_oid;
+    let mut file_path = file_path_from_repository.to_path_buf();
+    let points = commits
+        .iter()
+        .filter_map(|git_commit| {
+            let (git_diff, is_parent_missing) = get_commit_diff(git_commit, repo);
+
+            let delta = git_diff.deltas().find(|delta| {
+                delta.new_file().id() == file_oid
+                    && delta
+                        .new_file()
+                        .path()
+                        .filter(|path| *path == file_path)
+                        .is_some()
+            });
+            if let Some(delta) = delta.as_ref() {
+                file_oid = delta.old_file().id();
+                file_path = delta.old_file().path().unwrap().to_path_buf();
+            }
+
+            delta.map(|delta| {
+                let commit = Commit::new(git_commit, repo, reference_index);
+                let diff = Diff::new(&delta, git_commit.tree_id(), repo, highlighter, args)
+                    .with_parent_missing(is_parent_missing);
+                let point = TurningPoint::new(commit, diff, repo, highlighter, args);
+                let should_skip = args.skip_formatting_commits
+                    && (ignored_revs.contains(&git_commit.id())
+                        || point.diff().is_whitespace_only());
+                (point, should_skip)
+            })
         })
-        .and_then(|entry| {
-            if let Some(ObjectType::Blob) = entry.kind() {
-                Ok(entry)
+        .collect::<Vec<_>>();
+
+    History::new(
+        file_path_from_repository,
+        fold_skipped_points(points).into_iter(),
+    )
+}
+
+// Get the diff of a commit from its first parent, with whether the parent is missing.
+fn get_commit_diff<'r>(git_commit: &git2::Commit, repo: &'r Repository) -> (git2::Diff<'r>, bool) {
+    let old_tree = git_commit.parent(0).and_then(|p| p.tree()).ok();
+    // the parent is not fetched in a shallow clone
+    let is_parent_missing = git_commit.parent_count() > 0 && old_tree.is_none();
+    let new_tree = git_commit.tree().ok();
+    assert!(new_tree.is_some());
+
+    let mut git_diff = repo
+        .diff_tree_to_tree(old_tree.as_ref(), new_tree.as_ref(), None)
+        .unwrap();
+
+    // detect file renames
+    git_diff
+        .find_similar(Some(DiffFindOptions::new().renames(true)))
+        .unwrap();
+
+    (git_diff, is_parent_missing)
+}
+
+// Get all changes of a commit with their line stats, counted in a single pass over the patches.
+pub fn get_changed_files<'a>(
+    commit_oid: Oid,
+    repo: &'a Repository,
+    highlighter: &'a Highlighter,
+    args: &'a Args,
+) -> Vec<Diff<'a>> {
+    let git_commit = repo.find_commit(commit_oid).unwrap();
+    let (git_diff, is_parent_missing) = get_commit_diff(&git_commit, repo);
+
+    git_diff
+        .deltas()
+        .enumerate()
+        .map(|(index, delta)| {
+            let diff = Diff::new(&delta, git_commit.tree_id(), repo, highlighter, args)
+                .with_parent_missing(is_parent_missing);
+            let line_stats = if diff.is_binary() || diff.unavailable_reason().is_some() {
+                None
             } else {
-                Err(anyhow!(
-                    "Failed to find the path '{}' as a blob on HEAD",
-                    file_path.as_ref().to_string_lossy()
-                ))
-            }
-        })?
-        .id();
+                Patch::from_diff(&git_diff, index)
+                    .ok()
+                    .flatten()
+                    .and_then(|patch| patch.line_stats().ok())
+                    .map(|(_context, additions, deletions)| LineStats::new(additions, deletions))
+            };
+            diff.with_line_stats(line_stats)
+        })
+        .collect()
+}
 
-    let mut file_oid = latest_file_oid;
-    let mut file_path = file_path_from_repository;
-    let history = History::new(commits.iter().filter_map(|git_commit| {
-        let old_tree = git_commit.parent(0).and_then(|p| p.tree()).ok();
-        let new_tree = git_commit.tree().ok();
-        assert!(new_tree.is_some());
-
-        let mut git_diff = repo
-            .diff_tree_to_tree(old_tree.as_ref(), new_tree.as_ref(), None)
-            .unwrap();
-
-        // detect file renames
-        git_diff
-            .find_similar(Some(DiffFindOptions::new().renames(true)))
-            .unwrap();
-
-        let delta = git_diff.deltas().find(|delta| {
-            delta.new_file().id() == file_oid
-                && delta
-                    .new_file()
-                    .path()
-                    .filter(|path| *path == file_path)
-                    .is_some()
-        });
-        if let Some(delta) = delta.as_ref() {
-            file_oid = delta.old_file().id();
-            file_path = delta.old_file().path().unwrap().to_path_buf();
+// Fold a skipped point into the newer neighbour, or into the older one if it is the latest.
+fn fold_skipped_points(points: Vec<(TurningPoint, bool)>) -> Vec<TurningPoint> {
+    if points.iter().all(|(_, should_skip)| *should_skip) {
+        return points.into_iter().map(|(point, _)| point).collect();
+    }
+
+    let mut folded_points: Vec<TurningPoint> = vec![];
+    let mut pending_points = vec![]; // skipped points newer than any shown point
+    for (point, should_skip) in points.into_iter() {
+        match (should_skip, folded_points.last_mut()) {
+            (true, Some(newer_point)) => newer_point.fold_older(point),
+            (true, None) => pending_points.push(point),
+            (false, _) => {
+                let mut point = point;
+                while let Some(newer_point) = pending_points.pop() {
+                    point.fold_newer(newer_point);
+                }
+                folded_points.push(point);
+            }
         }
+    }
+    folded_points
+}
 
-        delta.map(|delta| {
-            let commit = Commit::new(git_commit, repo);
-            let diff = Diff::new(&delta, repo, args);
-            TurningPoint::new(commit, diff)
+// cf. `remote.<name>.promisor` and `extensions.partialClone` of git config
+pub fn is_partial_clone(repo: &Repository) -> bool {
+    let config = match repo.config() {
+        Ok(config) => config,
+        Err(_) => return false,
+    };
+    if config.get_string("extensions.partialclone").is_ok() {
+        return true;
+    }
+    config
+        .entries(Some(r"remote\..*\.promisor"))
+        .map(|entries| {
+            (&entries).filter_map(|entry| entry.ok()).any(|entry| {
+                entry
+                    .value()
+                    .and_then(|value| Config::parse_bool(value).ok())
+                    == Some(true)
+            })
         })
-    }));
+        .unwrap_or(false)
+}
 
-    Ok(history)
+// cf. `blame.ignoreRevsFile` of git config
+fn get_ignored_revs(repo: &Repository) -> HashSet<Oid> {
+    let workdir = repo.workdir().unwrap();
+    let mut files = vec![workdir.join(".git-blame-ignore-revs")];
+    if let Ok(config) = repo.config() {
+        if let Ok(entries) = config.multivar("blame.ignoreRevsFile", None) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                if let Some(value) = entry.value() {
+                    files.push(workdir.join(value));
+                }
+            }
+        }
+    }
+
+    files
+        .iter()
+        .filter_map(|file| fs::read_to_string(file).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .map(|line| {
+                    line.split('#')
+                        .next()
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string()
+                })
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .filter_map(|rev| {
+            repo.revparse_single(&rev)
+                .and_then(|object| object.peel_to_commit())
+                .map(|commit| commit.id())
+                .ok()
+        })
+        .collect()
 }
@@ -0,0 +1,121 @@
+use crate::args::Args;
+use elsa::FrozenMap;
+use git2::Oid;
+use once_cell::sync::Lazy;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+use tui::style::Color;
+
+const THEME_NAME: &str = "base16-ocean.dark";
+
+static SYNTAX_SET: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+static THEME: Lazy<Theme> =
+    Lazy::new(|| ThemeSet::load_defaults().themes.remove(THEME_NAME).unwrap());
+
+pub struct Highlighter {
+    // cache for each blob, syntax, and content, which may be converted by textconv or decoded
+    tokens: FrozenMap<(Oid, String, u64), Vec<Vec<Token>>>,
+    is_enabled: bool,
+}
+
+impl Highlighter {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            tokens: FrozenMap::new(),
+            is_enabled: args.syntax_highlight,
+        }
+    }
+
+    // Highlight a whole file to get tokens of each line, so that multi-line strings and comments are handled.
+    pub fn tokens(
+        &self,
+        oid: Oid,
+        path: Option<&str>,
+        language: Option<&str>,
+        content: &[u8],
+    ) -> Option<&[Vec<Token>]> {
+        if !self.is_enabled || oid.is_zero() {
+            return None;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        let content_hash = hasher.finish();
+        let content = String::from_utf8_lossy(content);
+        let syntax = find_syntax(path?, language, &content)?;
+        let key = (oid, syntax.name.clone(), content_hash);
+        if let Some(tokens) = self.tokens.get(&key) {
+            return Some(tokens);
+        }
+
+        let mut highlight_lines = HighlightLines::new(syntax, &THEME);
+        let tokens = LinesWithEndings::from(&content)
+            .map(|line| {
+                highlight_lines
+                    .highlight_line(line, &SYNTAX_SET)
+                    .map(|ranges| {
+                        ranges
+                            .into_iter()
+                            .map(|(style, text)| {
+                                let color = style.foreground;
+                                Token::new(text.len(), Color::Rgb(color.r, color.g, color.b))
+                            })
+                            .collect()
+                    })
+                    .ok()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(self.tokens.insert(key, tokens))
+    }
+}
+
+// cf. `linguist-language` of .gitattributes
+fn find_syntax(
+    path: &str,
+    language: Option<&str>,
+    content: &str,
+) -> Option<&'static SyntaxReference> {
+    let syntax = language
+        .and_then(|language| SYNTAX_SET.find_syntax_by_token(language))
+        .or_else(|| {
+            Path::new(path).extension().and_then(|extension| {
+                SYNTAX_SET.find_syntax_by_extension(&extension.to_string_lossy())
+            })
+        })
+        .or_else(|| {
+            Path::new(path)
+                .file_name()
+                .and_then(|name| SYNTAX_SET.find_syntax_by_extension(&name.to_string_lossy()))
+        })
+        .or_else(|| SYNTAX_SET.find_syntax_by_first_line(content.lines().next()?))?;
+    if syntax.name == SYNTAX_SET.find_syntax_plain_text().name {
+        None
+    } else {
+        Some(syntax)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Token {
+    len: usize, // in bytes
+    color: Color,
+}
+
+impl Token {
+    fn new(len: usize, color: Color) -> Self {
+        Self { len, color }
+    }
+
+    pub fn text_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+}
@@ -1,22 +1,45 @@
 use crate::app::commit::Commit;
 use crate::app::diff::Diff;
+use crate::app::git;
+use crate::app::highlight::Highlighter;
+use crate::args::Args;
+use anyhow::{anyhow, Result};
+use git2::{Oid, Repository};
+use once_cell::sync::OnceCell;
+use std::path::{Path, PathBuf};
 
 pub struct TurningPoint<'a> {
     commit: Commit<'a>,
     diff: Diff<'a>,
+    changed_files: OnceCell<Vec<Diff<'a>>>, // all changes of the commit including `diff`
+    folded_commits: Vec<Commit<'a>>,        // skipped commits whose changes are included in `diff`
     is_latest: Option<bool>,
     is_earliest: Option<bool>,
     index_of_history: Option<usize>,
+    repo: &'a Repository,
+    highlighter: &'a Highlighter,
+    args: &'a Args,
 }
 
 impl<'a> TurningPoint<'a> {
-    pub fn new(commit: Commit<'a>, diff: Diff<'a>) -> Self {
+    pub fn new(
+        commit: Commit<'a>,
+        diff: Diff<'a>,
+        repo: &'a Repository,
+        highlighter: &'a Highlighter,
+        args: &'a Args,
+    ) -> Self {
         Self {
             commit,
             diff,
+            changed_files: OnceCell::new(),
+            folded_commits: vec![],
             is_latest: None,
             is_earliest: None,
             index_of_history: None,
+            repo,
+            highlighter,
+            args,
         }
     }
 
@@ -28,21 +51,47 @@ impl<'a> TurningPoint<'a> {
         self.is_earliest.unwrap()
     }
 
-    pub fn commit(&self) -> &Commit {
+    pub fn commit(&self) -> &Commit<'a> {
         &self.commit
     }
 
-    pub fn diff(&self) -> &Diff {
+    pub fn diff(&self) -> &Diff<'a> {
         &self.diff
     }
+
+    pub fn changed_files(&self) -> &Vec<Diff<'a>> {
+        self.changed_files.get_or_init(|| {
+            git::get_changed_files(self.commit.oid(), self.repo, self.highlighter, self.args)
+        })
+    }
+
+    pub fn folded_commits(&self) -> &Vec<Commit<'a>> {
+        &self.folded_commits
+    }
+
+    pub fn fold_older(&mut self, older: TurningPoint<'a>) {
+        self.diff.fold_older(older.diff);
+        self.folded_commits.push(older.commit);
+        self.folded_commits.extend(older.folded_commits);
+    }
+
+    pub fn fold_newer(&mut self, newer: TurningPoint<'a>) {
+        self.diff.fold_newer(newer.diff);
+        self.folded_commits.insert(0, newer.commit);
+    }
 }
 
 pub struct History<'a> {
+    file_path: PathBuf, // a path from the repository root at the latest point
     points: Vec<TurningPoint<'a>>,
 }
 
 impl<'a> History<'a> {
-    pub fn new<I: Iterator<Item = TurningPoint<'a>>>(points: I) -> Self {
+    pub fn new<I: Iterator<Item = TurningPoint<'a>>>(
+        file_path: impl Into<PathBuf>,
+        points: I,
+    ) -> Result<Self> {
+        let file_path = file_path.into();
         let mut points = points
             .enumerate()
             .map(|(i, mut p)| {
@@ -50,31 +99,74 @@ impl<'a> History<'a> {
                 p
             })
             .collect::<Vec<_>>();
-        assert!(!points.is_empty());
+        if points.is_empty() {
+            return Err(anyhow!(
+                "Failed to find any commit changing the file '{}'",
+                file_path.to_string_lossy()
+            ));
+        }
 
         let len = points.len();
         for point in points.iter_mut() {
             point.is_latest = Some(point.index_of_history.unwrap() == 0);
             point.is_earliest = Some(point.index_of_history.unwrap() + 1 == len);
         }
-        History { points }
+        Ok(History { file_path, points })
+    }
+
+    pub fn file_path(&self) -> &Path {
+        &self.file_path
     }
 
-    pub fn latest(&self) -> Option<&TurningPoint> {
+    pub fn latest(&self) -> Option<&TurningPoint<'a>> {
         self.points.first()
     }
 
-    pub fn backward(&self, point: &TurningPoint) -> Option<&TurningPoint> {
+    pub fn find(&self, commit_oid: Oid) -> Option<&TurningPoint<'a>> {
+        self.points.iter().find(|p| p.commit().oid() == commit_oid)
+    }
+
+    pub fn backward(&self, point: &TurningPoint) -> Option<&TurningPoint<'a>> {
         point
             .index_of_history
             .and_then(|i| i.checked_add(1))
             .and_then(|i| self.points.get(i))
     }
 
-    pub fn forward(&self, point: &TurningPoint) -> Option<&TurningPoint> {
+    pub fn forward(&self, point: &TurningPoint) -> Option<&TurningPoint<'a>> {
         point
             .index_of_history
             .and_then(|i| i.checked_sub(1))
             .and_then(|i| self.points.get(i))
     }
 }
+
+// A position to restore after switching the browsed file
+#[derive(Debug, Clone)]
+pub struct Location {
+    file_path: PathBuf,      // a path from the repository root
+    commit_oid: Option<Oid>, // `None` for the latest point
+    line_index: usize,
+}
+
+impl Location {
+    pub fn new(file_path: impl Into<PathBuf>, commit_oid: Option<Oid>, line_index: usize) -> Self {
+        Self {
+            file_path: file_path.into(),
+            commit_oid,
+            line_index,
+        }
+    }
+
+    pub fn file_path(&self) -> &Path {
+        &self.file_path
+    }
+
+    pub fn commit_oid(&self) -> Option<Oid> {
+        self.commit_oid
+    }
+
+    pub fn line_index(&self) -> usize {
+        self.line_index
+    }
+}
@@ -0,0 +1,174 @@
+use std::convert::TryInto;
+use std::fmt;
+
+// Metadata read from headers of PNG, JPEG, GIF, and WebP images
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    format: &'static str,
+    width: u32,
+    height: u32,
+    bit_depth: u8, // per channel, or of palette indices for indexed colors
+    color: &'static str,
+}
+
+impl ImageInfo {
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        Self::parse_png(content)
+            .or_else(|| Self::parse_jpeg(content))
+            .or_else(|| Self::parse_gif(content))
+            .or_else(|| Self::parse_webp(content))
+    }
+
+    // cf. https://www.w3.org/TR/png/#11IHDR
+    fn parse_png(content: &[u8]) -> Option<Self> {
+        if !content.starts_with(b"\x89PNG\r\n\x1a\n") || content.get(12..16)? != b"IHDR" {
+            return None;
+        }
+        let color = match content.get(25)? {
+            0 => "grayscale",
+            2 => "RGB",
+            3 => "indexed",
+            4 => "grayscale+alpha",
+            6 => "RGBA",
+            _ => return None,
+        };
+        Some(Self {
+            format: "PNG",
+            width: read_u32_be(content, 16)?,
+            height: read_u32_be(content, 20)?,
+            bit_depth: *content.get(24)?,
+            color,
+        })
+    }
+
+    // Walk segments until a start-of-frame marker
+    fn parse_jpeg(content: &[u8]) -> Option<Self> {
+        if !content.starts_with(b"\xff\xd8") {
+            return None;
+        }
+        let mut offset = 2;
+        loop {
+            if *content.get(offset)? != 0xff {
+                return None;
+            }
+            let marker = *content.get(offset + 1)?;
+            match marker {
+                0xff => offset += 1, // fill bytes
+                0x01 | 0xd0..=0xd7 => offset += 2,
+                0xc0..=0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf => {
+                    let color = match content.get(offset + 9)? {
+                        1 => "grayscale",
+                        3 => "YCbCr",
+                        4 => "CMYK",
+                        _ => return None,
+                    };
+                    return Some(Self {
+                        format: "JPEG",
+                        width: read_u16_be(content, offset + 7)? as u32,
+                        height: read_u16_be(content, offset + 5)? as u32,
+                        bit_depth: *content.get(offset + 4)?,
+                        color,
+                    });
+                }
+                0xd9 | 0xda => return None, // end of image or start of scan
+                _ => offset += 2 + read_u16_be(content, offset + 2)? as usize,
+            }
+        }
+    }
+
+    // cf. https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+    fn parse_gif(content: &[u8]) -> Option<Self> {
+        if !content.starts_with(b"GIF87a") && !content.starts_with(b"GIF89a") {
+            return None;
+        }
+        let packed = *content.get(10)?;
+        let bit_depth = if packed & 0x80 != 0 {
+            (packed & 0x07) + 1 // size of the global color table
+        } else {
+            ((packed >> 4) & 0x07) + 1 // color resolution
+        };
+        Some(Self {
+            format: "GIF",
+            width: read_u16_le(content, 6)? as u32,
+            height: read_u16_le(content, 8)? as u32,
+            bit_depth,
+            color: "indexed",
+        })
+    }
+
+    // cf. https://developers.google.com/speed/webp/docs/riff_container
+    fn parse_webp(content: &[u8]) -> Option<Self> {
+        if !content.starts_with(b"RIFF") || content.get(8..12)? != b"WEBP" {
+            return None;
+        }
+        let (width, height, has_alpha) = match content.get(12..16)? {
+            b"VP8 " => {
+                if content.get(23..26)? != b"\x9d\x01\x2a" {
+                    return None;
+                }
+                (
+                    (read_u16_le(content, 26)? & 0x3fff) as u32,
+                    (read_u16_le(content, 28)? & 0x3fff) as u32,
+                    false,
+                )
+            }
+            b"VP8L" => {
+                if *content.get(20)? != 0x2f {
+                    return None;
+                }
+                let bits = u32::from_le_bytes(content.get(21..25)?.try_into().ok()?);
+                (
+                    (bits & 0x3fff) + 1,
+                    ((bits >> 14) & 0x3fff) + 1,
+                    (bits >> 28) & 0x01 != 0,
+                )
+            }
+            b"VP8X" => (
+                read_u24_le(content, 24)? + 1,
+                read_u24_le(content, 27)? + 1,
+                *content.get(20)? & 0x10 != 0,
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            format: "WebP",
+            width,
+            height,
+            bit_depth: 8,
+            color: if has_alpha { "RGBA" } else { "RGB" },
+        })
+    }
+}
+
+impl fmt::Display for ImageInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}×{} {}-bit {}",
+            self.format, self.width, self.height, self.bit_depth, self.color
+        )
+    }
+}
+
+fn read_u16_be(content: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        content.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u16_le(content: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        content.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u24_le(content: &[u8], offset: usize) -> Option<u32> {
+    let bytes = content.get(offset..offset + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+fn read_u32_be(content: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        content.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
@@ -0,0 +1,83 @@
+use git2::Repository;
+use std::fs;
+use std::path::PathBuf;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+const POINTER_MAX_SIZE: usize = 1024;
+
+// cf. https://github.com/git-lfs/git-lfs/blob/main/docs/spec.md
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    oid: String, // a SHA-256 hash
+    size: usize,
+}
+
+impl LfsPointer {
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        if content.len() > POINTER_MAX_SIZE {
+            return None;
+        }
+        let content = std::str::from_utf8(content).ok()?;
+        let mut lines = content.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+
+        let mut oid = None;
+        let mut size = None;
+        for line in lines {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "oid" => {
+                    oid = value
+                        .strip_prefix("sha256:")
+                        .filter(|hash| hash.len() == 64)
+                        .filter(|hash| hash.bytes().all(|b| b.is_ascii_hexdigit()))
+                        .map(|hash| hash.to_ascii_lowercase())
+                }
+                "size" => size = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            oid: oid?,
+            size: size?,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // `None` if the object is not downloaded yet
+    pub fn load(&self, repo: &Repository) -> Option<Vec<u8>> {
+        fs::read(self.object_path(repo))
+            .ok()
+            .filter(|content| content.len() == self.size)
+    }
+
+    pub fn is_downloaded(&self, repo: &Repository) -> bool {
+        fs::metadata(self.object_path(repo))
+            .map(|metadata| metadata.len() as usize == self.size)
+            .unwrap_or(false)
+    }
+
+    // cf. `lfs.storage` of git config
+    fn object_path(&self, repo: &Repository) -> PathBuf {
+        let storage = repo
+            .config()
+            .and_then(|config| config.get_path("lfs.storage"))
+            .map(|path| repo.path().join(path))
+            .unwrap_or_else(|_| repo.path().join("lfs"));
+        storage
+            .join("objects")
+            .join(&self.oid[0..2])
+            .join(&self.oid[2..4])
+            .join(&self.oid)
+    }
+}
+
+// cf. `buffer_is_binary` of git
+pub fn looks_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|b| *b == 0)
+}
@@ -1,23 +1,47 @@
 use anyhow::Result;
 use std::panic;
 
+mod attributes;
 mod commit;
 mod controller;
 mod dashboard;
 mod diff;
+mod encoding;
 mod git;
+mod highlight;
 mod history;
+mod image;
+mod lfs;
+mod reference;
 mod state;
+mod structure;
 mod terminal;
+mod words;
 
 use crate::args::Args;
+use controller::Control;
 use dashboard::Dashboard;
+use diff::DiffOptions;
+use highlight::Highlighter;
+use history::Location;
+use reference::ReferenceIndex;
 use state::State;
 use terminal::Terminal;
 
 pub fn run(args: Args) -> Result<()> {
     let repo = git::get_repository()?;
-    let history = git::get_history(&args.file_path, &repo, &args)?;
+    let reference_index = ReferenceIndex::new(&repo, &args)?;
+    let highlighter = Highlighter::new(&args);
+    let file_path = git::get_path_from_repository(&args.file_path, &repo)?;
+    let mut location = Location::new(file_path, None, 0);
+    let mut history = git::get_history_at(
+        location.file_path(),
+        location.commit_oid(),
+        &repo,
+        &reference_index,
+        &highlighter,
+        &args,
+    )?;
 
     terminal::initialize()?;
 
@@ -29,21 +53,82 @@ pub fn run(args: Args) -> Result<()> {
 
     (|| -> Result<()> {
         let mut terminal = Terminal::new()?;
-        let mut current_state = State::first(&history, &terminal, &args);
-        let dashboard = Dashboard::new(&current_state);
-        dashboard.draw(&mut terminal)?;
+        // locations to go back after switching the browsed file
+        let mut back_stack: Vec<Location> = vec![];
+        let mut diff_options = DiffOptions::new(&args);
+        let mut layout_mode = args.layout_mode;
 
-        while let Some(next_state) = controller::poll_next_event(current_state, &history)? {
-            current_state = next_state;
+        // an error on switching the browsed file, which is shown without leaving the current file
+        let mut error_message: Option<String> = None;
+
+        loop {
+            let mut current_state = State::at(
+                &location,
+                &history,
+                &terminal,
+                diff_options.clone(),
+                layout_mode,
+                &args,
+            );
+            if let Some(message) = error_message.take() {
+                current_state = current_state.with_error(message);
+            }
             let dashboard = Dashboard::new(&current_state);
             dashboard.draw(&mut terminal)?;
-        }
 
-        Ok(())
+            let last_back_stack = back_stack.clone();
+            let (next_location, last_location) = loop {
+                match controller::poll_next_event(current_state, &history)? {
+                    Control::Continue(next_state) => current_state = next_state,
+                    Control::Open(last_state, next_location) => {
+                        let last_location = last_state.location(&history);
+                        back_stack.push(last_location.clone());
+                        diff_options = last_state
+                            .diff_options()
+                            .clone()
+                            .with_expanded_folds(vec![]);
+                        layout_mode = last_state.layout_mode();
+                        break (next_location, last_location);
+                    }
+                    Control::Back(last_state) => match back_stack.pop() {
+                        Some(back_location) => {
+                            diff_options = last_state
+                                .diff_options()
+                                .clone()
+                                .with_expanded_folds(vec![]);
+                            layout_mode = last_state.layout_mode();
+                            break (back_location, last_state.location(&history));
+                        }
+                        None => current_state = last_state,
+                    },
+                    Control::Quit => return Ok(()),
+                }
+                let dashboard = Dashboard::new(&current_state);
+                dashboard.draw(&mut terminal)?;
+            };
+
+            match git::get_history_at(
+                next_location.file_path(),
+                next_location.commit_oid(),
+                &repo,
+                &reference_index,
+                &highlighter,
+                &args,
+            ) {
+                Ok(next_history) => {
+                    history = next_history;
+                    location = next_location;
+                }
+                Err(err) => {
+                    back_stack = last_back_stack;
+                    location = last_location;
+                    error_message = Some(format!("{:#}", err));
+                }
+            }
+        }
     })()
-    .map_err(|e| {
+    .inspect_err(|_| {
         let _ = exit();
-        e
     })?;
 
     exit()
@@ -0,0 +1,490 @@
+use crate::args::Args;
+use anyhow::{Context, Result};
+use chrono::TimeZone;
+use elsa::FrozenMap;
+use git2::{Oid, ReferenceType as GitReferenceType, Repository};
+use std::{
+    collections::{BinaryHeap, HashMap},
+    fmt,
+};
+
+const HEAD_NAME: &str = "HEAD";
+const CONTAINMENT_SLOP: i64 = 24 * 60 * 60; // cf. `CUTOFF_DATE_SLOP` of `git name-rev`
+
+pub struct ReferenceIndex {
+    references: HashMap<Oid, References>,
+    empty: References,
+    tag_tips: Vec<ReferenceTip>, // sorted by commit time in ascending order
+    release_branch_tips: Vec<ReferenceTip>, // local and remote branches matching `--release-branches`
+    tips: HashMap<Oid, (i64, Reach)>,       // tips pointing at each commit, with its commit time
+    containments: FrozenMap<Oid, Box<Containment>>, // cache for each commit
+}
+
+struct ReferenceTip {
+    name: String,
+    oid: Oid,
+    time: i64,
+}
+
+impl ReferenceIndex {
+    pub fn new(repo: &Repository, args: &Args) -> Result<Self> {
+        let head = repo.head().ok();
+        let head_name = head.as_ref().and_then(|h| h.name().map(String::from));
+
+        let mut references: HashMap<Oid, References> = HashMap::new();
+        let mut tag_tips = vec![];
+        let mut release_branch_tips = vec![];
+
+        for r in repo
+            .references()
+            .context("Failed to read references")?
+            .filter_map(|r| r.ok())
+            // skip symbolic references such as `refs/remotes/origin/HEAD`
+            .filter(|r| r.kind() == Some(GitReferenceType::Direct))
+        {
+            let reference_type = match r {
+                _ if r.is_branch() => ReferenceType::LocalBranch,
+                _ if r.is_remote() => ReferenceType::RemoteBranch,
+                _ if r.is_tag() => ReferenceType::Tag,
+                _ => continue,
+            };
+            let name = match r.shorthand() {
+                Some(name) => name,
+                None => continue,
+            };
+            // peel annotated tags to commits
+            let (oid, time) = match r.peel_to_commit() {
+                Ok(commit) => (commit.id(), commit.time().seconds()),
+                Err(_) => continue,
+            };
+
+            let tip = ReferenceTip {
+                name: name.to_string(),
+                oid,
+                time,
+            };
+            match reference_type {
+                ReferenceType::Tag => tag_tips.push(tip),
+                ReferenceType::LocalBranch if is_release_branch(name, args) => {
+                    release_branch_tips.push(tip)
+                }
+                ReferenceType::RemoteBranch
+                    if name
+                        .split_once('/')
+                        .filter(|(_, name)| is_release_branch(name, args))
+                        .is_some() =>
+                {
+                    release_branch_tips.push(tip)
+                }
+                _ => {}
+            }
+
+            let entry = references.entry(oid).or_insert_with(References::empty);
+            match reference_type {
+                ReferenceType::LocalBranch => entry.local_branches.push(LocalBranch::new(
+                    name,
+                    r.name().is_some() && r.name() == head_name.as_deref(),
+                )),
+                ReferenceType::RemoteBranch => entry.remote_branches.push(RemoteBranch::new(name)),
+                ReferenceType::Tag => {
+                    let annotation = r.peel_to_tag().ok().map(|tag| TagAnnotation::new(&tag));
+                    entry.tags.push(Tag::new(name, annotation))
+                }
+            }
+        }
+
+        // detached HEAD
+        if let Some(head) = head.filter(|h| h.name() == Some(HEAD_NAME)) {
+            if let Some(oid) = head.target() {
+                references
+                    .entry(oid)
+                    .or_insert_with(References::empty)
+                    .is_head = true;
+            }
+        }
+
+        tag_tips.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.name.cmp(&b.name)));
+
+        let mut tips: HashMap<Oid, (i64, Reach)> = HashMap::new();
+        for (i, tip) in tag_tips.iter().enumerate() {
+            let (_, reach) = tips
+                .entry(tip.oid)
+                .or_insert_with(|| (tip.time, Reach::new(release_branch_tips.len())));
+            reach.earliest_tag.get_or_insert(i);
+        }
+        for (i, tip) in release_branch_tips.iter().enumerate() {
+            let (_, reach) = tips
+                .entry(tip.oid)
+                .or_insert_with(|| (tip.time, Reach::new(release_branch_tips.len())));
+            reach.release_branches[i] = true;
+        }
+
+        Ok(Self {
+            references,
+            empty: References::empty(),
+            tag_tips,
+            release_branch_tips,
+            tips,
+            containments: FrozenMap::new(),
+        })
+    }
+
+    pub fn get(&self, oid: Oid) -> &References {
+        self.references.get(&oid).unwrap_or(&self.empty)
+    }
+
+    pub fn containment(&self, repo: &Repository, oid: Oid, time: i64) -> &Containment {
+        if let Some(containment) = self.containments.get(&oid) {
+            return containment;
+        }
+        self.containments
+            .insert(oid, Box::new(self.calc_containment(repo, oid, time)))
+    }
+
+    // Find tags and release branches containing a commit, cf. `git describe --contains` and
+    // `git branch --contains`. Reachable tips are passed down from the tips in order of commit
+    // time, and passed again if a commit gets more tips later because of clock skew. The walk
+    // stops at commits older than the commit by more than a slop as `git name-rev` does.
+    fn calc_containment(&self, repo: &Repository, oid: Oid, time: i64) -> Containment {
+        let cutoff = time.saturating_sub(CONTAINMENT_SLOP);
+        let mut reaches: HashMap<Oid, Reach> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for (tip_oid, (tip_time, reach)) in self.tips.iter() {
+            if *tip_time >= cutoff {
+                reaches.insert(*tip_oid, reach.clone());
+                queue.push((*tip_time, *tip_oid));
+            }
+        }
+
+        while let Some((commit_time, commit_oid)) = queue.pop() {
+            if commit_time < cutoff {
+                break;
+            }
+            if commit_oid == oid {
+                continue;
+            }
+            let reach = match reaches.get(&commit_oid) {
+                Some(reach) => reach.clone(),
+                None => continue,
+            };
+            let commit = match repo.find_commit(commit_oid) {
+                Ok(commit) => commit,
+                Err(_) => continue,
+            };
+            for parent in commit.parents() {
+                // a tag on the commit itself is the earliest one
+                let has_own_tag = self
+                    .tips
+                    .get(&parent.id())
+                    .is_some_and(|(_, tip_reach)| tip_reach.earliest_tag.is_some());
+                let is_changed = reaches
+                    .entry(parent.id())
+                    .or_insert_with(|| Reach::new(self.release_branch_tips.len()))
+                    .merge(&reach, has_own_tag);
+                if is_changed {
+                    queue.push((parent.time().seconds(), parent.id()));
+                }
+            }
+        }
+
+        reaches.get(&oid).map_or_else(
+            || Containment::new(None, vec![]),
+            |reach| reach.to_containment(self),
+        )
+    }
+}
+
+// indices of the tips reaching a commit
+#[derive(Debug, Clone)]
+struct Reach {
+    earliest_tag: Option<usize>, // tips are sorted by time, so the smallest index is the earliest
+    release_branches: Vec<bool>,
+}
+
+impl Reach {
+    fn new(release_branch_count: usize) -> Self {
+        Self {
+            earliest_tag: None,
+            release_branches: vec![false; release_branch_count],
+        }
+    }
+
+    // `true` if tips are added
+    fn merge(&mut self, other: &Reach, should_keep_tag: bool) -> bool {
+        let mut is_changed = false;
+        if !should_keep_tag
+            && other
+                .earliest_tag
+                .is_some_and(|j| self.earliest_tag.is_none_or(|i| j < i))
+        {
+            self.earliest_tag = other.earliest_tag;
+            is_changed = true;
+        }
+        for (a, b) in self
+            .release_branches
+            .iter_mut()
+            .zip(&other.release_branches)
+        {
+            if *b && !*a {
+                *a = true;
+                is_changed = true;
+            }
+        }
+        is_changed
+    }
+
+    fn to_containment(&self, index: &ReferenceIndex) -> Containment {
+        let earliest_tag = self
+            .earliest_tag
+            .map(|i| Tag::new(&index.tag_tips[i].name, None));
+        let release_branches = index
+            .release_branch_tips
+            .iter()
+            .zip(&self.release_branches)
+            .filter(|(_, contains)| **contains)
+            .map(|(tip, _)| tip.name.clone())
+            .collect();
+        Containment::new(earliest_tag, release_branches)
+    }
+}
+
+fn is_release_branch(name: &str, args: &Args) -> bool {
+    args.release_branch_patterns
+        .iter()
+        .any(|pattern| matches_wildcard(pattern, name))
+}
+
+// `*` matches any sequence of characters
+fn matches_wildcard(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let mut parts = parts.collect::<Vec<_>>();
+    let last = match parts.pop() {
+        Some(last) => last,
+        // no wildcard
+        None => return rest.is_empty(),
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[derive(Debug)]
+pub struct Containment {
+    earliest_tag: Option<Tag>,
+    release_branches: Vec<String>,
+}
+
+impl Containment {
+    pub fn new(earliest_tag: Option<Tag>, release_branches: Vec<String>) -> Self {
+        Self {
+            earliest_tag,
+            release_branches,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.earliest_tag.is_none() && self.release_branches.is_empty()
+    }
+
+    pub fn earliest_tag_name(&self) -> Option<String> {
+        self.earliest_tag.as_ref().map(|x| format!("{}", x))
+    }
+
+    pub fn release_branch_names(&self) -> &Vec<String> {
+        &self.release_branches
+    }
+}
+
+#[derive(Debug)]
+pub struct References {
+    local_branches: Vec<LocalBranch>,
+    remote_branches: Vec<RemoteBranch>,
+    tags: Vec<Tag>,
+    is_head: bool,
+}
+
+impl References {
+    pub fn new(
+        local_branches: Vec<LocalBranch>,
+        remote_branches: Vec<RemoteBranch>,
+        tags: Vec<Tag>,
+        is_head: bool,
+    ) -> Self {
+        Self {
+            local_branches,
+            remote_branches,
+            tags,
+            is_head,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(vec![], vec![], vec![], false)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.local_branches.is_empty()
+            && self.remote_branches.is_empty()
+            && self.tags.is_empty()
+            && !self.is_head
+    }
+
+    pub fn head_names(&self) -> Vec<String> {
+        if self.is_head {
+            vec![String::from(HEAD_NAME)]
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn local_branch_names(&self) -> Vec<String> {
+        self.local_branches
+            .iter()
+            .map(|x| format!("{}", x))
+            .collect()
+    }
+
+    pub fn remote_branch_names(&self) -> Vec<String> {
+        self.remote_branches
+            .iter()
+            .map(|x| format!("{}", x))
+            .collect()
+    }
+
+    pub fn tag_names(&self) -> Vec<String> {
+        self.tags.iter().map(|x| format!("{}", x)).collect()
+    }
+
+    pub fn tags(&self) -> &Vec<Tag> {
+        &self.tags
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum ReferenceType {
+    LocalBranch,
+    RemoteBranch,
+    Tag,
+}
+
+#[derive(Debug)]
+pub struct LocalBranch {
+    name: String,
+    is_head: bool,
+}
+
+impl LocalBranch {
+    pub fn new(name: impl Into<String>, is_head: bool) -> Self {
+        Self {
+            name: name.into(),
+            is_head,
+        }
+    }
+}
+
+impl fmt::Display for LocalBranch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_head {
+            write!(f, "{} -> {}", HEAD_NAME, self.name)
+        } else {
+            write!(f, "{}", self.name)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RemoteBranch {
+    name: String,
+}
+
+impl RemoteBranch {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+impl fmt::Display for RemoteBranch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Tag {
+    name: String,
+    annotation: Option<TagAnnotation>,
+}
+
+impl Tag {
+    pub fn new(name: impl Into<String>, annotation: Option<TagAnnotation>) -> Self {
+        Self {
+            name: name.into(),
+            annotation,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // `None` for a lightweight tag
+    pub fn annotation(&self) -> Option<&TagAnnotation> {
+        self.annotation.as_ref()
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tag: {}", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct TagAnnotation {
+    message: String,
+    tagger_name: Option<String>,
+    tagger_email: Option<String>,
+    tagger_date: Option<chrono::DateTime<chrono::Local>>,
+}
+
+impl TagAnnotation {
+    pub fn new(tag: &git2::Tag) -> Self {
+        let tagger = tag.tagger();
+        Self {
+            message: tag.message().unwrap_or_default().trim_end().to_string(),
+            tagger_name: tagger.as_ref().and_then(|t| t.name().map(String::from)),
+            tagger_email: tagger.as_ref().and_then(|t| t.email().map(String::from)),
+            tagger_date: tagger.as_ref().map(|t| {
+                chrono::DateTime::<chrono::Local>::from(
+                    chrono::Utc.timestamp(t.when().seconds(), 0),
+                )
+            }),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn tagger_name(&self) -> Option<&str> {
+        self.tagger_name.as_deref()
+    }
+
+    pub fn tagger_email(&self) -> Option<&str> {
+        self.tagger_email.as_deref()
+    }
+
+    pub fn tagger_date(&self) -> Option<&chrono::DateTime<chrono::Local>> {
+        self.tagger_date.as_ref()
+    }
+}
@@ -1,52 +1,181 @@
 use crate::app::dashboard::Dashboard;
-use crate::app::history::{History, TurningPoint};
+use crate::app::diff::{Diff, DiffLayout, DiffOptions, DiffRowKind};
+use crate::app::history::{History, Location, TurningPoint};
 use crate::app::terminal::Terminal;
-use crate::args::Args;
+use crate::args::{Args, LayoutMode};
 use std::cmp;
 
-pub struct State<'a> {
-    point: &'a TurningPoint<'a>,
+const HORIZONTAL_SCROLL_WIDTH: usize = 8;
+
+pub struct State<'h, 'a> {
+    point: &'h TurningPoint<'a>,
     line_index: usize,
     max_line_number_len: usize,
+    column_index: usize, // the number of columns scrolled horizontally
+    should_wrap: bool,
+    should_show_whitespace: bool,
+    terminal_width: usize,
     terminal_height: usize,
+    popup: Option<Popup>,
+    popup_line_index: usize,
+    error_message: Option<String>,     // shown in `Popup::Error`
+    changed_file_index: Option<usize>, // `None` for the target file, otherwise an index of `point.changed_files()`
+    diff_options: DiffOptions,
+    layout_mode: LayoutMode,
     args: &'a Args,
 }
 
-impl<'a> State<'a> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Popup {
+    TagMessage,
+    CommitDetails,
+    ChangedFiles,
+    Error,
+}
+
+impl<'h, 'a> State<'h, 'a> {
     pub fn new(
-        point: &'a TurningPoint<'a>,
+        point: &'h TurningPoint<'a>,
         line_index: usize,
         max_line_number_len: usize,
-        terminal_height: usize,
+        terminal: &Terminal,
+        diff_options: DiffOptions,
+        layout_mode: LayoutMode,
         args: &'a Args,
     ) -> Self {
+        let terminal_width = terminal.width();
+        let terminal_height = terminal.height();
+        let diff_options =
+            diff_options.with_layout(Self::resolve_layout(layout_mode, terminal_width));
         Self {
             point,
             line_index,
             max_line_number_len,
+            column_index: 0,
+            should_wrap: args.should_wrap,
+            should_show_whitespace: args.should_show_whitespace,
+            terminal_width,
             terminal_height,
+            popup: None,
+            popup_line_index: 0,
+            error_message: None,
+            changed_file_index: None,
+            diff_options,
+            layout_mode,
             args,
         }
     }
 
-    pub fn first(history: &'a History<'a>, terminal: &Terminal, args: &'a Args) -> Self {
+    fn resolve_layout(layout_mode: LayoutMode, terminal_width: usize) -> DiffLayout {
+        match layout_mode {
+            LayoutMode::Unified => DiffLayout::Unified,
+            LayoutMode::Split => DiffLayout::Split,
+            LayoutMode::Auto if Dashboard::can_split(terminal_width) => DiffLayout::Split,
+            LayoutMode::Auto => DiffLayout::Unified,
+        }
+    }
+
+    pub fn first(
+        history: &'h History<'a>,
+        terminal: &Terminal,
+        diff_options: DiffOptions,
+        layout_mode: LayoutMode,
+        args: &'a Args,
+    ) -> Self {
         let point = history.latest().unwrap();
         let line_index = 0;
-        let max_line_number_len = point.diff().max_line_number_len();
-        let terminal_height = terminal.height();
+        let max_line_number_len = point.diff().max_line_number_len(&diff_options);
         Self::new(
             point,
             line_index,
             max_line_number_len,
-            terminal_height,
+            terminal,
+            diff_options,
+            layout_mode,
             args,
         )
     }
 
-    pub fn point(&self) -> &TurningPoint {
+    pub fn at(
+        location: &Location,
+        history: &'h History<'a>,
+        terminal: &Terminal,
+        diff_options: DiffOptions,
+        layout_mode: LayoutMode,
+        args: &'a Args,
+    ) -> Self {
+        let state = Self::first(history, terminal, diff_options, layout_mode, args);
+        let point = match location.commit_oid().and_then(|oid| history.find(oid)) {
+            Some(point) => point,
+            None => return state,
+        };
+        let max_line_number_len = point.diff().max_line_number_len(&state.diff_options);
+        State {
+            point,
+            line_index: location.line_index(),
+            max_line_number_len,
+            ..state
+        }
+        .clamp_line_index()
+    }
+
+    // the location of the target file
+    pub fn location(&self, history: &History) -> Location {
+        let line_index = if self.changed_file_index.is_some() {
+            0
+        } else {
+            self.line_index
+        };
+        Location::new(
+            history.file_path(),
+            Some(self.point.commit().oid()),
+            line_index,
+        )
+    }
+
+    // the location of a file selected in the list of changed files, or a file shown instead of the target file
+    pub fn changed_file_location(&self) -> Option<Location> {
+        let (changed_file, line_index) = if self.popup == Some(Popup::ChangedFiles) {
+            (self.point.changed_files().get(self.popup_line_index)?, 0)
+        } else {
+            (
+                self.point.changed_files().get(self.changed_file_index?)?,
+                self.line_index,
+            )
+        };
+        if changed_file.is_same_file(self.point.diff()) {
+            return None;
+        }
+        Some(Location::new(
+            changed_file.new_path()?,
+            Some(self.point.commit().oid()),
+            line_index,
+        ))
+    }
+
+    pub fn point(&self) -> &'h TurningPoint<'a> {
         self.point
     }
 
+    pub fn diff(&self) -> &'h Diff<'a> {
+        match self.changed_file_index {
+            Some(index) => &self.point.changed_files()[index],
+            None => self.point.diff(),
+        }
+    }
+
+    pub fn changed_file_index(&self) -> Option<usize> {
+        self.changed_file_index
+    }
+
+    pub fn diff_options(&self) -> &DiffOptions {
+        &self.diff_options
+    }
+
+    pub fn layout_mode(&self) -> LayoutMode {
+        self.layout_mode
+    }
+
     pub fn line_index(&self) -> usize {
         self.line_index
     }
@@ -55,170 +184,581 @@ impl<'a> State<'a> {
         self.max_line_number_len
     }
 
+    pub fn column_index(&self) -> usize {
+        self.column_index
+    }
+
+    pub fn should_wrap(&self) -> bool {
+        self.should_wrap
+    }
+
+    pub fn should_show_whitespace(&self) -> bool {
+        self.should_show_whitespace
+    }
+
     pub fn can_move_up(&self) -> bool {
-        self.point.diff().can_move_up(self.line_index, self)
+        self.diff().can_move_up(self.line_index, self)
     }
 
     pub fn can_move_down(&self) -> bool {
-        self.point.diff().can_move_down(self.line_index, self)
+        self.diff().can_move_down(self.line_index, self)
     }
 
-    pub fn backward_commit(self, history: &'a History) -> Self {
+    pub fn backward_commit(self, history: &'h History<'a>) -> Self {
         if let Some(next_point) = history.backward(self.point) {
-            let index_pair = self.point.diff().nearest_old_index_pair(self.line_index);
-            let line_index = next_point
-                .diff()
-                .find_index_from_new_index(index_pair.partial_index())
-                .map(|index| index.saturating_sub(index_pair.relative_index()))
-                .unwrap_or(0);
+            // folds of the diff are not expanded in another commit
+            let diff_options = self.diff_options.clone().with_expanded_folds(vec![]);
+            let line_index = if self.changed_file_index.is_some()
+                || self.diff().is_structured(&self.diff_options)
+                || next_point.diff().is_structured(&diff_options)
+            {
+                0
+            } else if self.args.jump_to_first_change {
+                Self::first_change_index(next_point.diff(), &diff_options)
+            } else {
+                let index_pair = self
+                    .point
+                    .diff()
+                    .nearest_old_index_pair(self.line_index, &self.diff_options);
+                next_point
+                    .diff()
+                    .find_index_from_new_index(index_pair.partial_index(), &diff_options)
+                    .map(|index| index.saturating_sub(index_pair.relative_index()))
+                    .unwrap_or(0)
+            };
             let max_line_number_len = cmp::max(
                 self.max_line_number_len,
-                next_point.diff().max_line_number_len(),
+                next_point.diff().max_line_number_len(&diff_options),
             );
 
-            State::new(
-                next_point,
+            State {
+                point: next_point,
                 line_index,
                 max_line_number_len,
-                self.terminal_height,
-                self.args,
-            )
+                changed_file_index: None,
+                diff_options,
+                ..self
+            }
+            .clamp_line_index()
         } else {
             self
         }
     }
 
-    pub fn forward_commit(self, history: &'a History) -> Self {
+    pub fn forward_commit(self, history: &'h History<'a>) -> Self {
         if let Some(next_point) = history.forward(self.point) {
-            let index_pair = self.point.diff().nearest_new_index_pair(self.line_index);
-            let line_index = next_point
-                .diff()
-                .find_index_from_old_index(index_pair.partial_index())
-                .map(|index| index.saturating_sub(index_pair.relative_index()))
-                .unwrap_or(0);
+            // folds of the diff are not expanded in another commit
+            let diff_options = self.diff_options.clone().with_expanded_folds(vec![]);
+            let line_index = if self.changed_file_index.is_some()
+                || self.diff().is_structured(&self.diff_options)
+                || next_point.diff().is_structured(&diff_options)
+            {
+                0
+            } else if self.args.jump_to_first_change {
+                Self::first_change_index(next_point.diff(), &diff_options)
+            } else {
+                let index_pair = self
+                    .point
+                    .diff()
+                    .nearest_new_index_pair(self.line_index, &self.diff_options);
+                next_point
+                    .diff()
+                    .find_index_from_old_index(index_pair.partial_index(), &diff_options)
+                    .map(|index| index.saturating_sub(index_pair.relative_index()))
+                    .unwrap_or(0)
+            };
             let max_line_number_len = cmp::max(
                 self.max_line_number_len,
-                next_point.diff().max_line_number_len(),
+                next_point.diff().max_line_number_len(&diff_options),
             );
 
-            State::new(
-                next_point,
+            State {
+                point: next_point,
                 line_index,
                 max_line_number_len,
-                self.terminal_height,
-                self.args,
-            )
+                changed_file_index: None,
+                diff_options,
+                ..self
+            }
+            .clamp_line_index()
         } else {
             self
         }
     }
 
+    // e.g. the first change is in the last lines, which cannot be scrolled to the top of the view
+    fn clamp_line_index(self) -> Self {
+        let line_index = cmp::min(self.line_index, self.diff().allowed_max_index(&self));
+        State { line_index, ..self }
+    }
+
+    fn first_change_index(diff: &Diff, diff_options: &DiffOptions) -> usize {
+        diff.change_start_indices(diff_options)
+            .first()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn next_change(self) -> Self {
+        let next_index = self
+            .diff()
+            .change_start_indices(&self.diff_options)
+            .into_iter()
+            .find(|index| *index > self.line_index);
+        match next_index {
+            Some(index) => {
+                let line_index = cmp::max(
+                    self.line_index,
+                    cmp::min(index, self.diff().allowed_max_index(&self)),
+                );
+                State { line_index, ..self }
+            }
+            None => self,
+        }
+    }
+
+    pub fn previous_change(self) -> Self {
+        let previous_index = self
+            .diff()
+            .change_start_indices(&self.diff_options)
+            .into_iter()
+            .rev()
+            .find(|index| *index < self.line_index);
+        match previous_index {
+            Some(index) => State {
+                line_index: index,
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    pub fn jump_to_move_pair(self) -> Self {
+        match self
+            .diff()
+            .move_pair_row_index(&self.diff_options, self.line_index)
+        {
+            Some(index) => {
+                let line_index = cmp::min(index, self.diff().allowed_max_index(&self));
+                State { line_index, ..self }
+            }
+            None => self,
+        }
+    }
+
     pub fn scroll_line_up(self) -> Self {
-        if self.can_move_up() {
+        if self.popup.is_some() {
+            let popup_line_index = self.popup_line_index.saturating_sub(1);
+            State {
+                popup_line_index,
+                ..self
+            }
+        } else if self.can_move_up() {
             let line_index = self.line_index - 1;
-            State::new(
-                self.point,
-                line_index,
-                self.max_line_number_len,
-                self.terminal_height,
-                self.args,
-            )
+            State { line_index, ..self }
         } else {
             self
         }
     }
 
     pub fn scroll_line_down(self) -> Self {
-        if self.can_move_down() {
+        if self.popup.is_some() {
+            let popup_line_index =
+                cmp::min(self.popup_line_index + 1, Dashboard::popup_max_index(&self));
+            State {
+                popup_line_index,
+                ..self
+            }
+        } else if self.can_move_down() {
             let line_index = self.line_index + 1;
-            State::new(
-                self.point,
-                line_index,
-                self.max_line_number_len,
-                self.terminal_height,
-                self.args,
-            )
+            State { line_index, ..self }
         } else {
             self
         }
     }
 
     pub fn scroll_page_up(self) -> Self {
+        if self.popup.is_some() {
+            let popup_line_index = self
+                .popup_line_index
+                .saturating_sub(Dashboard::popup_height(&self));
+            return State {
+                popup_line_index,
+                ..self
+            };
+        }
+
         let diff_height = Dashboard::diff_height(self.terminal_height);
 
         let line_index = cmp::min(
             self.line_index,
             cmp::max(
                 self.line_index.saturating_sub(diff_height),
-                self.point.diff().allowed_min_index(&self),
+                self.diff().allowed_min_index(&self),
             ),
         );
 
-        State::new(
-            self.point,
-            line_index,
-            self.max_line_number_len,
-            self.terminal_height,
-            self.args,
-        )
+        State { line_index, ..self }
     }
 
     pub fn scroll_page_down(self) -> Self {
+        if self.popup.is_some() {
+            let popup_line_index = cmp::min(
+                self.popup_line_index + Dashboard::popup_height(&self),
+                Dashboard::popup_max_index(&self),
+            );
+            return State {
+                popup_line_index,
+                ..self
+            };
+        }
+
         let diff_height = Dashboard::diff_height(self.terminal_height);
 
         let line_index = cmp::max(
             self.line_index,
             cmp::min(
                 self.line_index + diff_height,
-                self.point.diff().allowed_max_index(&self),
+                self.diff().allowed_max_index(&self),
             ),
         );
 
-        State::new(
-            self.point,
-            line_index,
-            self.max_line_number_len,
-            self.terminal_height,
-            self.args,
-        )
+        State { line_index, ..self }
     }
 
     pub fn scroll_to_top(self) -> Self {
-        let line_index = cmp::min(self.line_index, self.point.diff().allowed_min_index(&self));
+        if self.popup.is_some() {
+            return State {
+                popup_line_index: 0,
+                ..self
+            };
+        }
 
-        State::new(
-            self.point,
-            line_index,
-            self.max_line_number_len,
-            self.terminal_height,
-            self.args,
-        )
+        let line_index = cmp::min(self.line_index, self.diff().allowed_min_index(&self));
+
+        State { line_index, ..self }
     }
 
     pub fn scroll_to_bottom(self) -> Self {
-        let line_index = cmp::max(self.line_index, self.point.diff().allowed_max_index(&self));
+        if self.popup.is_some() {
+            let popup_line_index = Dashboard::popup_max_index(&self);
+            return State {
+                popup_line_index,
+                ..self
+            };
+        }
+
+        let line_index = cmp::max(self.line_index, self.diff().allowed_max_index(&self));
 
-        State::new(
-            self.point,
+        State { line_index, ..self }
+    }
+
+    pub fn scroll_column_left(self) -> Self {
+        let column_index = self.column_index.saturating_sub(HORIZONTAL_SCROLL_WIDTH);
+        State {
+            column_index,
+            ..self
+        }
+    }
+
+    pub fn scroll_column_right(self) -> Self {
+        if self.should_wrap {
+            return self;
+        }
+        let max_column_index = self
+            .diff()
+            .max_text_width(&self.diff_options)
+            .saturating_sub(Dashboard::text_width(&self));
+        let column_index = cmp::max(
+            self.column_index,
+            cmp::min(
+                self.column_index + HORIZONTAL_SCROLL_WIDTH,
+                max_column_index,
+            ),
+        );
+        State {
+            column_index,
+            ..self
+        }
+    }
+
+    pub fn toggle_wrap(self) -> Self {
+        let state = State {
+            should_wrap: !self.should_wrap,
+            column_index: 0,
+            ..self
+        };
+        let line_index = cmp::min(state.line_index, state.diff().allowed_max_index(&state));
+        State {
             line_index,
-            self.max_line_number_len,
-            self.terminal_height,
-            self.args,
-        )
+            ..state
+        }
+    }
+
+    pub fn toggle_show_whitespace(self) -> Self {
+        State {
+            should_show_whitespace: !self.should_show_whitespace,
+            ..self
+        }
+    }
+
+    pub fn cycle_diff_algorithm(self) -> Self {
+        let algorithm = self.diff_options.algorithm().next();
+        let diff_options = self.diff_options.clone().with_algorithm(algorithm);
+        self.update_diff_options(diff_options)
+    }
+
+    pub fn cycle_whitespace_mode(self) -> Self {
+        let whitespace_mode = self.diff_options.whitespace_mode().next();
+        let diff_options = self
+            .diff_options
+            .clone()
+            .with_whitespace_mode(whitespace_mode);
+        self.update_diff_options(diff_options)
+    }
+
+    pub fn toggle_ignore_blank_lines(self) -> Self {
+        let ignore_blank_lines = !self.diff_options.ignore_blank_lines();
+        let diff_options = self
+            .diff_options
+            .clone()
+            .with_ignore_blank_lines(ignore_blank_lines);
+        self.update_diff_options(diff_options)
+    }
+
+    pub fn cycle_granularity(self) -> Self {
+        let granularity = self.diff_options.granularity().next();
+        let diff_options = self.diff_options.clone().with_granularity(granularity);
+        self.update_diff_options(diff_options)
+    }
+
+    pub fn toggle_word_diff(self) -> Self {
+        let word_diff = !self.diff_options.word_diff();
+        let diff_options = self.diff_options.clone().with_word_diff(word_diff);
+        self.update_diff_options(diff_options)
+    }
+
+    pub fn toggle_structured_diff(self) -> Self {
+        let structured = !self.diff_options.structured();
+        let diff_options = self.diff_options.clone().with_structured(structured);
+        self.update_diff_options(diff_options)
+    }
+
+    pub fn toggle_color_moved(self) -> Self {
+        let color_moved = !self.diff_options.color_moved();
+        let diff_options = self.diff_options.clone().with_color_moved(color_moved);
+        self.update_diff_options(diff_options)
+    }
+
+    pub fn toggle_layout(self) -> Self {
+        let (layout_mode, layout) = match self.diff_options.layout() {
+            DiffLayout::Unified => (LayoutMode::Split, DiffLayout::Split),
+            DiffLayout::Split => (LayoutMode::Unified, DiffLayout::Unified),
+        };
+        let diff_options = self.diff_options.clone().with_layout(layout);
+        State {
+            layout_mode,
+            ..self
+        }
+        .update_diff_options(diff_options)
+    }
+
+    pub fn toggle_hunk_only(self) -> Self {
+        let context_lines = match self.diff_options.context_lines() {
+            Some(_) => None,
+            None => Some(self.args.context_lines),
+        };
+        let diff_options = self
+            .diff_options
+            .clone()
+            .with_context_lines(context_lines)
+            .with_expanded_folds(vec![]);
+        self.update_diff_options(diff_options)
+    }
+
+    // toggle the fold at the top of the view, or expand the first fold in the view
+    pub fn toggle_fold(self) -> Self {
+        let diff_height = Dashboard::diff_height(self.terminal_height);
+        let fold = self
+            .diff()
+            .fold_at(self.line_index, &self.diff_options)
+            .or_else(|| {
+                self.diff()
+                    .rows(&self.diff_options)
+                    .iter()
+                    .skip(self.line_index)
+                    .take(diff_height)
+                    .find_map(|row| match row.kind() {
+                        DiffRowKind::Fold(start, _) => Some((start, false)),
+                        _ => None,
+                    })
+            });
+        let (start, is_expanded) = match fold {
+            Some(fold) => fold,
+            None => return self,
+        };
+        let mut expanded_folds = self.diff_options.expanded_folds().to_vec();
+        if is_expanded {
+            expanded_folds.retain(|&s| s != start);
+        } else {
+            expanded_folds.push(start);
+            expanded_folds.sort_unstable();
+        }
+        let diff_options = self
+            .diff_options
+            .clone()
+            .with_expanded_folds(expanded_folds);
+        self.update_diff_options(diff_options)
+    }
+
+    // keep the position in the new file after recomputing lines of the diff
+    fn update_diff_options(self, diff_options: DiffOptions) -> Self {
+        let diff = self.diff();
+        let line_index = match (
+            diff.is_structured(&self.diff_options),
+            diff.is_structured(&diff_options),
+        ) {
+            // a row for each key path in any layout
+            (true, true) => self.line_index,
+            // lines of a structured diff have no line numbers
+            (true, false) | (false, true) => 0,
+            (false, false) => {
+                let index_pair = diff.nearest_new_index_pair(self.line_index, &self.diff_options);
+                diff.find_index_from_new_index(index_pair.partial_index(), &diff_options)
+                    .map(|index| index.saturating_sub(index_pair.relative_index()))
+                    .unwrap_or(0)
+            }
+        };
+        let state = State {
+            diff_options,
+            ..self
+        };
+        let line_index = cmp::min(line_index, state.diff().allowed_max_index(&state));
+        State {
+            line_index,
+            ..state
+        }
+    }
+
+    pub fn terminal_width(&self) -> usize {
+        self.terminal_width
     }
 
     pub fn terminal_height(&self) -> usize {
         self.terminal_height
     }
 
-    pub fn update_terminal_height(self, terminal_height: usize) -> Self {
-        State::new(
-            self.point,
-            self.line_index,
-            self.max_line_number_len,
+    pub fn update_terminal_size(self, terminal_width: usize, terminal_height: usize) -> Self {
+        let layout = Self::resolve_layout(self.layout_mode, terminal_width);
+        let state = State {
+            terminal_width,
             terminal_height,
-            self.args,
-        )
+            ..self
+        };
+        if layout == state.diff_options.layout() {
+            state
+        } else {
+            let diff_options = state.diff_options.clone().with_layout(layout);
+            state.update_diff_options(diff_options)
+        }
+    }
+
+    pub fn popup(&self) -> Option<Popup> {
+        self.popup
+    }
+
+    pub fn popup_line_index(&self) -> usize {
+        self.popup_line_index
+    }
+
+    pub fn error_message(&self) -> Option<&str> {
+        self.error_message.as_deref()
+    }
+
+    pub fn with_error(self, message: impl Into<String>) -> Self {
+        State {
+            popup: Some(Popup::Error),
+            popup_line_index: 0,
+            error_message: Some(message.into()),
+            ..self
+        }
+    }
+
+    pub fn toggle_popup(self, popup: Popup) -> Self {
+        let popup = if self.popup == Some(popup) {
+            None
+        } else {
+            Some(popup)
+        };
+        let popup_line_index = match popup {
+            // put the cursor on the file shown now
+            Some(Popup::ChangedFiles) => self.changed_file_index.unwrap_or_else(|| {
+                self.point
+                    .changed_files()
+                    .iter()
+                    .position(|diff| diff.is_same_file(self.point.diff()))
+                    .unwrap_or(0)
+            }),
+            _ => 0,
+        };
+        State {
+            popup,
+            popup_line_index,
+            ..self
+        }
+    }
+
+    pub fn cancel(self) -> Self {
+        if self.popup.is_some() {
+            State {
+                popup: None,
+                popup_line_index: 0,
+                ..self
+            }
+        } else if self.changed_file_index.is_some() {
+            // go back to the target file
+            let diff_options = self.diff_options.clone().with_expanded_folds(vec![]);
+            State {
+                line_index: 0,
+                changed_file_index: None,
+                diff_options,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    pub fn select_changed_file(self) -> Self {
+        if self.popup != Some(Popup::ChangedFiles) {
+            return self;
+        }
+
+        let changed_file = match self.point.changed_files().get(self.popup_line_index) {
+            Some(changed_file) => changed_file,
+            None => return self,
+        };
+        let changed_file_index = if changed_file.is_same_file(self.point.diff()) {
+            None
+        } else {
+            Some(self.popup_line_index)
+        };
+        let max_line_number_len = cmp::max(
+            self.max_line_number_len,
+            changed_file.max_line_number_len(&self.diff_options),
+        );
+
+        let diff_options = self.diff_options.clone().with_expanded_folds(vec![]);
+
+        State {
+            line_index: 0,
+            max_line_number_len,
+            popup: None,
+            popup_line_index: 0,
+            changed_file_index,
+            diff_options,
+            ..self
+        }
     }
 
     pub fn args(&self) -> &'a Args {
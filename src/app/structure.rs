@@ -0,0 +1,215 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use similar::{Algorithm, DiffOp};
+use std::cmp;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+// formats of data files compared by their structures instead of lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl DataFormat {
+    pub fn of(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "json" => Some(DataFormat::Json),
+            "yaml" | "yml" => Some(DataFormat::Yaml),
+            "toml" => Some(DataFormat::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataFormat::Json => "JSON",
+            DataFormat::Yaml => "YAML",
+            DataFormat::Toml => "TOML",
+        }
+    }
+
+    // Values of all formats are converted into JSON values, whose objects are sorted by keys.
+    pub fn parse(&self, text: &str) -> Result<Value> {
+        match self {
+            DataFormat::Json => Ok(serde_json::from_str(text)?),
+            DataFormat::Yaml => {
+                // documents separated by `---` are compared as elements of an array
+                let mut values = serde_yaml::Deserializer::from_str(text)
+                    .map(|document| serde_yaml::Value::deserialize(document).map(from_yaml))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(match values.len() {
+                    0 => Value::Null,
+                    1 => values.remove(0),
+                    _ => Value::Array(values),
+                })
+            }
+            DataFormat::Toml => Ok(from_toml(toml::from_str(text)?)),
+        }
+    }
+}
+
+// a key path whose value is added, removed, or changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataChange {
+    path: String,
+    old_value: Option<Value>,
+    new_value: Option<Value>,
+}
+
+impl DataChange {
+    // e.g. `.dependencies.serde.features[0]`, or `.` for the root
+    pub fn path(&self) -> &str {
+        if self.path.is_empty() {
+            "."
+        } else {
+            &self.path
+        }
+    }
+
+    pub fn old_value(&self) -> Option<String> {
+        self.old_value.as_ref().map(Value::to_string)
+    }
+
+    pub fn new_value(&self) -> Option<String> {
+        self.new_value.as_ref().map(Value::to_string)
+    }
+}
+
+// Compare values recursively, where elements of arrays are aligned by a diff of them.
+pub fn diff_values(old_value: &Value, new_value: &Value) -> Vec<DataChange> {
+    let mut changes = vec![];
+    push_changes(&mut changes, String::new(), old_value, new_value);
+    changes
+}
+
+fn push_changes(changes: &mut Vec<DataChange>, path: String, old_value: &Value, new_value: &Value) {
+    let change = |path: String, old_value: Option<&Value>, new_value: Option<&Value>| DataChange {
+        path,
+        old_value: old_value.cloned(),
+        new_value: new_value.cloned(),
+    };
+
+    match (old_value, new_value) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            let keys = old_map
+                .keys()
+                .chain(new_map.keys())
+                .collect::<BTreeSet<_>>();
+            for key in keys {
+                let path = format!("{}{}", path, key_path(key));
+                match (old_map.get(key), new_map.get(key)) {
+                    (Some(old_value), Some(new_value)) => {
+                        push_changes(changes, path, old_value, new_value)
+                    }
+                    (old_value, new_value) => changes.push(change(path, old_value, new_value)),
+                }
+            }
+        }
+        (Value::Array(old_array), Value::Array(new_array)) => {
+            let old_keys = old_array.iter().map(Value::to_string).collect::<Vec<_>>();
+            let new_keys = new_array.iter().map(Value::to_string).collect::<Vec<_>>();
+            for op in similar::capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys) {
+                if let DiffOp::Equal { .. } = op {
+                    continue;
+                }
+                // replaced elements are compared in pairs, and the rest are removed or added
+                let (_, old_range, new_range) = op.as_tag_tuple();
+                for k in 0..cmp::max(old_range.len(), new_range.len()) {
+                    let old_value = old_range.clone().nth(k).map(|i| &old_array[i]);
+                    let new_value = new_range.clone().nth(k).map(|i| &new_array[i]);
+                    let index = new_range
+                        .clone()
+                        .nth(k)
+                        .unwrap_or_else(|| old_range.start + k);
+                    let path = format!("{}[{}]", path, index);
+                    match (old_value, new_value) {
+                        (Some(old_value), Some(new_value)) => {
+                            push_changes(changes, path, old_value, new_value)
+                        }
+                        (old_value, new_value) => changes.push(change(path, old_value, new_value)),
+                    }
+                }
+            }
+        }
+        _ if old_value != new_value => changes.push(change(path, Some(old_value), Some(new_value))),
+        _ => {}
+    }
+}
+
+// `.key` for identifiers, and `."some key"` for others as jq does
+fn key_path(key: &str) -> String {
+    let is_identifier = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if is_identifier {
+        format!(".{}", key)
+    } else {
+        format!(".{}", Value::String(String::from(key)))
+    }
+}
+
+fn from_yaml(value: serde_yaml::Value) -> Value {
+    match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else {
+                from_float(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(sequence) => {
+            Value::Array(sequence.into_iter().map(from_yaml).collect())
+        }
+        serde_yaml::Value::Mapping(mapping) => Value::Object(
+            mapping
+                .into_iter()
+                .map(|(key, value)| {
+                    // keys other than strings, e.g. `1: one`, are converted into strings
+                    let key = match from_yaml(key) {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    (key, from_yaml(value))
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        // tags such as `!Ref` are ignored
+        serde_yaml::Value::Tagged(tagged) => from_yaml(tagged.value),
+    }
+}
+
+fn from_toml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => from_float(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(array) => Value::Array(array.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, from_toml(value)))
+                .collect(),
+        ),
+    }
+}
+
+// JSON has no NaN and infinities
+fn from_float(f: f64) -> Value {
+    Number::from_f64(f).map_or_else(|| Value::String(f.to_string()), Value::Number)
+}
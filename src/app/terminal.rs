@@ -39,6 +39,10 @@ impl Terminal {
         Ok(())
     }
 
+    pub fn width(&self) -> usize {
+        self.terminal.size().unwrap().width.into()
+    }
+
     pub fn height(&self) -> usize {
         self.terminal.size().unwrap().height.into()
     }
@@ -0,0 +1,64 @@
+use crate::args::Granularity;
+use regex::Regex;
+
+// Split a line into units of inline changes, where the line ending is always a unit.
+pub fn split_words<'t>(
+    line: &'t str,
+    granularity: Granularity,
+    word_regex: Option<&Regex>,
+) -> Vec<&'t str> {
+    let body = line.trim_end_matches(&['\r', '\n'][..]);
+    let mut words = match (granularity, word_regex) {
+        (Granularity::Char, _) => split_runs(body, |_| None),
+        (Granularity::Word, _) => split_runs(body, |c| Some(c.is_whitespace())),
+        (Granularity::Token, Some(regex)) => {
+            // characters not matched by the regex are split as `Granularity::Char` except whitespace
+            let mut words = vec![];
+            let mut last_end = 0;
+            for m in regex.find_iter(body).filter(|m| !m.as_str().is_empty()) {
+                words.extend(split_runs(&body[last_end..m.start()], |c| {
+                    c.is_whitespace().then_some(true)
+                }));
+                words.push(m.as_str());
+                last_end = m.end();
+            }
+            words.extend(split_runs(&body[last_end..], |c| {
+                c.is_whitespace().then_some(true)
+            }));
+            words
+        }
+        (Granularity::Token, None) => split_runs(body, |c| {
+            if c.is_whitespace() {
+                Some(true)
+            } else if c.is_alphanumeric() || c == '_' {
+                Some(false)
+            } else {
+                None
+            }
+        }),
+    };
+    if body.len() < line.len() {
+        words.push(&line[body.len()..]);
+    }
+    words
+}
+
+// Split a text into runs of characters of the same class, where characters without a class are
+// split one by one.
+fn split_runs(text: &str, class: impl Fn(char) -> Option<bool>) -> Vec<&str> {
+    let mut runs = vec![];
+    let mut start = 0;
+    let mut last_class = None;
+    for (i, c) in text.char_indices() {
+        let c_class = class(c);
+        if i > start && (c_class.is_none() || c_class != last_class) {
+            runs.push(&text[start..i]);
+            start = i;
+        }
+        last_class = c_class;
+    }
+    if start < text.len() {
+        runs.push(&text[start..]);
+    }
+    runs
+}
@@ -1,4 +1,5 @@
 use clap::{App, Arg};
+use std::{cmp, convert::TryFrom, env, time::Duration};
 
 #[derive(Debug)]
 pub struct Args {
@@ -6,10 +7,28 @@ pub struct Args {
     pub should_use_full_commit_hash: bool,
     pub beyond_last_line: bool,
     pub should_emphasize_diff: bool,
+    pub emphasis_granularity: Granularity,
+    pub word_diff: bool,
+    pub structured_diff: bool,
+    pub color_moved: bool,
     pub user_for_name: UserType,
     pub user_for_date: UserType,
     pub date_format: String,
-    pub tab_spaces: String,
+    pub tab_size: usize,
+    pub release_branch_patterns: Vec<String>,
+    pub diff_algorithm: DiffAlgorithm,
+    pub diff_timeout: Option<Duration>,
+    pub whitespace_mode: WhitespaceMode,
+    pub ignore_blank_lines: bool,
+    pub skip_formatting_commits: bool,
+    pub layout_mode: LayoutMode,
+    pub hunk_only: bool,
+    pub context_lines: usize,
+    pub jump_to_first_change: bool,
+    pub syntax_highlight: bool,
+    pub should_wrap: bool,
+    pub should_show_whitespace: bool,
+    pub hex_limit: usize,
 }
 
 #[derive(Debug)]
@@ -18,6 +37,103 @@ pub enum UserType {
     Committer,
 }
 
+// cf. `git diff --ignore-cr-at-eol`, `--ignore-space-change`, and `--ignore-all-space`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WhitespaceMode {
+    None,
+    IgnoreCrAtEol,
+    IgnoreSpaceChange,
+    IgnoreAllSpace,
+}
+
+impl WhitespaceMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WhitespaceMode::None => "none",
+            WhitespaceMode::IgnoreCrAtEol => "ignore-cr-at-eol",
+            WhitespaceMode::IgnoreSpaceChange => "ignore-space-change",
+            WhitespaceMode::IgnoreAllSpace => "ignore-all-space",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            WhitespaceMode::None => WhitespaceMode::IgnoreCrAtEol,
+            WhitespaceMode::IgnoreCrAtEol => WhitespaceMode::IgnoreSpaceChange,
+            WhitespaceMode::IgnoreSpaceChange => WhitespaceMode::IgnoreAllSpace,
+            WhitespaceMode::IgnoreAllSpace => WhitespaceMode::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutMode {
+    Auto, // split if the terminal is wide enough
+    Unified,
+    Split,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffAlgorithm {
+    Myers,
+    Patience,
+    Lcs,
+}
+
+impl DiffAlgorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            // `minimal` and `histogram` are for compatibility with `diff.algorithm` of git
+            "myers" | "default" | "minimal" => Some(DiffAlgorithm::Myers),
+            "patience" | "histogram" => Some(DiffAlgorithm::Patience),
+            "lcs" => Some(DiffAlgorithm::Lcs),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiffAlgorithm::Myers => "myers",
+            DiffAlgorithm::Patience => "patience",
+            DiffAlgorithm::Lcs => "lcs",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            DiffAlgorithm::Myers => DiffAlgorithm::Patience,
+            DiffAlgorithm::Patience => DiffAlgorithm::Lcs,
+            DiffAlgorithm::Lcs => DiffAlgorithm::Myers,
+        }
+    }
+}
+
+// units of inline changes, cf. `git diff --word-diff-regex`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Granularity {
+    Char,
+    Word,  // split by whitespace
+    Token, // split into identifiers and symbols, or by `diff.<driver>.wordRegex` of git config
+}
+
+impl Granularity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Granularity::Char => "char",
+            Granularity::Word => "word",
+            Granularity::Token => "token",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Granularity::Char => Granularity::Word,
+            Granularity::Word => Granularity::Token,
+            Granularity::Token => Granularity::Char,
+        }
+    }
+}
+
 impl Args {
     pub fn load() -> Args {
         let matches = App::new(env!("CARGO_PKG_NAME"))
@@ -51,11 +167,34 @@ impl Args {
                     .long("emphasize-diff")
                     .help("Set whether the view will emphasize different parts"),
             )
+            .arg(
+                Arg::new("granularity")
+                    .long("granularity")
+                    .value_name("unit")
+                    .possible_values(["char", "word", "token"])
+                    .default_value("word")
+                    .help("Set units of emphasized parts and word diffs")
+            )
+            .arg(
+                Arg::new("word-diff")
+                    .long("word-diff")
+                    .help("Show changed words inline as [-old-]{+new+} like `git diff --word-diff`")
+            )
+            .arg(
+                Arg::new("structured-diff")
+                    .long("structured-diff")
+                    .help("Show changed key paths of JSON, YAML, and TOML files instead of changed lines")
+            )
+            .arg(
+                Arg::new("color-moved")
+                    .long("color-moved")
+                    .help("Color moved blocks of lines like `git diff --color-moved` [default: `diff.colorMoved` of git config]")
+            )
             .arg(
                 Arg::new("name-of")
                     .long("name-of")
                     .value_name("user")
-                    .possible_values(&["author", "committer"])
+                    .possible_values(["author", "committer"])
                     .default_value("author")
                     .help("Use whether authors or committers for names"),
             )
@@ -63,7 +202,7 @@ impl Args {
                 Arg::new("date-of")
                     .long("date-of")
                     .value_name("user")
-                    .possible_values(&["author", "committer"])
+                    .possible_values(["author", "committer"])
                     .default_value("author")
                     .help("Use whether authors or committers for dates"),
             )
@@ -81,6 +220,100 @@ impl Args {
                     .default_value("4")
                     .help("Set the number of spaces for a tab character (\\t)")
             )
+            .arg(
+                Arg::new("release-branches")
+                    .long("release-branches")
+                    .value_name("patterns")
+                    .default_value("release/*")
+                    .help("Set comma-separated patterns of release branches to check if they contain commits")
+            )
+            .arg(
+                Arg::new("diff-algorithm")
+                    .long("diff-algorithm")
+                    .value_name("algorithm")
+                    .possible_values(["myers", "patience", "lcs"])
+                    .help("Set a diff algorithm, where `histogram` and `minimal` in git config are read as patience and myers [default: `hist.diffAlgorithm` or `diff.algorithm` of git config, or myers]")
+            )
+            .arg(
+                Arg::new("diff-timeout")
+                    .long("diff-timeout")
+                    .value_name("milliseconds")
+                    .help("Set a timeout to compute a diff, after which a less minimal diff is shown [default: `hist.diffTimeout` of git config]")
+            )
+            .arg(
+                Arg::new("ignore-all-space")
+                    .long("ignore-all-space")
+                    .short('w')
+                    .help("Ignore whitespace when comparing lines")
+            )
+            .arg(
+                Arg::new("ignore-space-change")
+                    .long("ignore-space-change")
+                    .short('b')
+                    .help("Ignore changes in amount of whitespace")
+            )
+            .arg(
+                Arg::new("ignore-cr-at-eol")
+                    .long("ignore-cr-at-eol")
+                    .help("Ignore carriage-return at the end of lines")
+            )
+            .arg(
+                Arg::new("ignore-blank-lines")
+                    .long("ignore-blank-lines")
+                    .help("Ignore changes whose lines are all blank")
+            )
+            .arg(
+                Arg::new("skip-formatting-commits")
+                    .long("skip-formatting-commits")
+                    .help("Skip commits whose changes are whitespace-only or listed in `blame.ignoreRevsFile` [default: `hist.skipFormattingCommits` of git config]")
+            )
+            .arg(
+                Arg::new("layout")
+                    .long("layout")
+                    .value_name("layout")
+                    .possible_values(["auto", "unified", "split"])
+                    .default_value("auto")
+                    .help("Show diffs in a unified view or side by side, or choose by the terminal width")
+            )
+            .arg(
+                Arg::new("hunk-only")
+                    .long("hunk-only")
+                    .help("Show only changed lines and their context lines, and fold the other lines")
+            )
+            .arg(
+                Arg::new("context-lines")
+                    .long("context-lines")
+                    .value_name("lines")
+                    .default_value("3")
+                    .help("Set the number of context lines around changes shown with --hunk-only")
+            )
+            .arg(
+                Arg::new("jump-to-first-change")
+                    .long("jump-to-first-change")
+                    .help("Show the first change of a diff after moving to another commit")
+            )
+            .arg(
+                Arg::new("syntax-highlight")
+                    .long("syntax-highlight")
+                    .help("Highlight the syntax of files by their extensions or `linguist-language` of .gitattributes [default: `hist.syntaxHighlight` of git config]")
+            )
+            .arg(
+                Arg::new("wrap")
+                    .long("wrap")
+                    .help("Wrap long lines instead of scrolling horizontally")
+            )
+            .arg(
+                Arg::new("show-whitespace")
+                    .long("show-whitespace")
+                    .help("Show tabs, trailing spaces, carriage-returns, and non-breaking spaces with visible characters")
+            )
+            .arg(
+                Arg::new("hex-limit")
+                    .long("hex-limit")
+                    .value_name("bytes")
+                    .default_value("8192")
+                    .help("Set the maximum number of bytes shown in a hex dump of binary files")
+            )
             .arg(
                 Arg::new("file")
                     .help("Set a target file path")
@@ -93,6 +326,13 @@ impl Args {
         let should_use_full_commit_hash = matches.is_present("full-hash");
         let beyond_last_line = matches.is_present("beyond-last-line");
         let should_emphasize_diff = matches.is_present("emphasize-diff");
+        let emphasis_granularity = match matches.value_of("granularity").unwrap() {
+            "char" => Granularity::Char,
+            "token" => Granularity::Token,
+            _ => Granularity::Word,
+        };
+        let word_diff = matches.is_present("word-diff");
+        let structured_diff = matches.is_present("structured-diff");
         let user_for_name = if matches.value_of("name-of").unwrap() == "author" {
             UserType::Author
         } else {
@@ -105,20 +345,139 @@ impl Args {
         };
         let date_format = String::from(matches.value_of("date-format").unwrap());
 
-        let tab_size = matches
-            .value_of_t::<usize>("tab-size")
+        let tab_size = cmp::max(
+            matches
+                .value_of_t::<usize>("tab-size")
+                .unwrap_or_else(|e| e.exit()),
+            1,
+        );
+
+        let release_branch_patterns = matches
+            .value_of("release-branches")
+            .unwrap()
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(String::from)
+            .collect();
+
+        let git_config = load_git_config();
+
+        let diff_algorithm = matches
+            .value_of("diff-algorithm")
+            .map(String::from)
+            .or_else(|| {
+                git_config.as_ref().and_then(|config| {
+                    config
+                        .get_string("hist.diffAlgorithm")
+                        .or_else(|_| config.get_string("diff.algorithm"))
+                        .ok()
+                })
+            })
+            .and_then(|name| DiffAlgorithm::parse(&name))
+            .unwrap_or(DiffAlgorithm::Myers);
+
+        let diff_timeout = if matches.is_present("diff-timeout") {
+            Some(
+                matches
+                    .value_of_t::<u64>("diff-timeout")
+                    .unwrap_or_else(|e| e.exit()),
+            )
+        } else {
+            git_config
+                .as_ref()
+                .and_then(|config| config.get_i64("hist.diffTimeout").ok())
+                .and_then(|millis| u64::try_from(millis).ok())
+        }
+        .map(Duration::from_millis);
+
+        let whitespace_mode = if matches.is_present("ignore-all-space") {
+            WhitespaceMode::IgnoreAllSpace
+        } else if matches.is_present("ignore-space-change") {
+            WhitespaceMode::IgnoreSpaceChange
+        } else if matches.is_present("ignore-cr-at-eol") {
+            WhitespaceMode::IgnoreCrAtEol
+        } else {
+            WhitespaceMode::None
+        };
+        let ignore_blank_lines = matches.is_present("ignore-blank-lines");
+
+        let skip_formatting_commits = matches.is_present("skip-formatting-commits")
+            || git_config
+                .as_ref()
+                .and_then(|config| config.get_bool("hist.skipFormattingCommits").ok())
+                .unwrap_or(false);
+
+        let layout_mode = match matches.value_of("layout").unwrap() {
+            "unified" => LayoutMode::Unified,
+            "split" => LayoutMode::Split,
+            _ => LayoutMode::Auto,
+        };
+
+        let hunk_only = matches.is_present("hunk-only");
+        let context_lines = matches
+            .value_of_t::<usize>("context-lines")
+            .unwrap_or_else(|e| e.exit());
+
+        let jump_to_first_change = matches.is_present("jump-to-first-change");
+
+        let syntax_highlight = matches.is_present("syntax-highlight")
+            || git_config
+                .as_ref()
+                .and_then(|config| config.get_bool("hist.syntaxHighlight").ok())
+                .unwrap_or(false);
+
+        // `diff.colorMoved` can be a mode such as `zebra`, which is not distinguished
+        let color_moved = matches.is_present("color-moved")
+            || git_config
+                .as_ref()
+                .and_then(|config| config.get_string("diff.colorMoved").ok())
+                .is_some_and(|mode| !matches!(mode.as_str(), "no" | "false" | "off" | "0"));
+
+        let should_wrap = matches.is_present("wrap");
+
+        let should_show_whitespace = matches.is_present("show-whitespace");
+
+        let hex_limit = matches
+            .value_of_t::<usize>("hex-limit")
             .unwrap_or_else(|e| e.exit());
-        let tab_spaces = " ".repeat(tab_size);
 
         Args {
             file_path,
             should_use_full_commit_hash,
             beyond_last_line,
             should_emphasize_diff,
+            emphasis_granularity,
+            word_diff,
+            structured_diff,
+            color_moved,
             user_for_name,
             user_for_date,
             date_format,
-            tab_spaces,
+            tab_size,
+            release_branch_patterns,
+            diff_algorithm,
+            diff_timeout,
+            whitespace_mode,
+            ignore_blank_lines,
+            skip_formatting_commits,
+            layout_mode,
+            hunk_only,
+            context_lines,
+            jump_to_first_change,
+            syntax_highlight,
+            should_wrap,
+            should_show_whitespace,
+            hex_limit,
         }
     }
 }
+
+// git config of the repository for the current directory, or global git config
+fn load_git_config() -> Option<git2::Config> {
+    env::current_dir()
+        .ok()
+        .and_then(|dir| git2::Repository::discover(dir).ok())
+        .and_then(|repo| repo.config().ok())
+        .or_else(|| git2::Config::open_default().ok())
+}